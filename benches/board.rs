@@ -3,6 +3,33 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use brainybishop::board::Board;
 use brainybishop::movegen::generate_moves;
 
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+fn perft_copy(board: &Board, depth: u32) -> u64 {
+    let moves = generate_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .iter()
+        .map(|mv| perft_copy(&board.make_move(*mv), depth - 1))
+        .sum()
+}
+
+fn perft_unmake(board: &mut Board, depth: u32) -> u64 {
+    let moves = generate_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for mv in moves.iter() {
+        let undo = board.make_move_mut(*mv);
+        nodes += perft_unmake(board, depth - 1);
+        board.unmake_move(*mv, undo);
+    }
+    nodes
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("from_fen_default_position", |b| {
         b.iter(|| {
@@ -18,10 +45,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 
     c.bench_function("generate_moves_kiwipete", |b| {
-        let board = Board::from_fen(
-            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
-        )
-        .unwrap();
+        let board = Board::from_fen(KIWIPETE).unwrap();
         b.iter(|| generate_moves(black_box(&board)))
     });
 
@@ -31,6 +55,17 @@ fn criterion_benchmark(c: &mut Criterion) {
                 .unwrap();
         b.iter(|| generate_moves(black_box(&board)))
     });
+
+    let mut group = c.benchmark_group("perft_kiwipete_3");
+    group.bench_function("copy_make", |b| {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        b.iter(|| perft_copy(black_box(&board), 3))
+    });
+    group.bench_function("make_unmake", |b| {
+        let mut board = Board::from_fen(KIWIPETE).unwrap();
+        b.iter(|| perft_unmake(black_box(&mut board), 3))
+    });
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -95,11 +95,11 @@ fn write_array_usize(f: &mut File, name: &str, arr: &[usize]) {
 }
 
 fn generate_rook_masks() -> Vec<u64> {
-    (0..64).map(|sq| rook_mask(sq)).collect()
+    (0..64).map(rook_mask).collect()
 }
 
 fn generate_bishop_masks() -> Vec<u64> {
-    (0..64).map(|sq| bishop_mask(sq)).collect()
+    (0..64).map(bishop_mask).collect()
 }
 
 fn rook_mask(sq: usize) -> u64 {
@@ -13,12 +13,30 @@ pub const BLACK_ROOK: usize = 9;
 pub const BLACK_QUEEN: usize = 10;
 pub const BLACK_KING: usize = 11;
 
+// Piece for each bitboard index
+const PIECES: [Piece; 12] = [
+    Piece(PieceType::Pawn, Color::White),
+    Piece(PieceType::Knight, Color::White),
+    Piece(PieceType::Bishop, Color::White),
+    Piece(PieceType::Rook, Color::White),
+    Piece(PieceType::Queen, Color::White),
+    Piece(PieceType::King, Color::White),
+    Piece(PieceType::Pawn, Color::Black),
+    Piece(PieceType::Knight, Color::Black),
+    Piece(PieceType::Bishop, Color::Black),
+    Piece(PieceType::Rook, Color::Black),
+    Piece(PieceType::Queen, Color::Black),
+    Piece(PieceType::King, Color::Black),
+];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Bitboard {
     pub pieces: [u64; 12],
     pub white: u64,
     pub black: u64,
     pub all: u64,
+    // Square to piece lookup, always in sync with the piece bitboards
+    pub mailbox: [Option<Piece>; 64],
 }
 
 impl Bitboard {
@@ -28,62 +46,39 @@ impl Bitboard {
             white: 0,
             black: 0,
             all: 0,
+            mailbox: [None; 64],
         }
     }
 
     pub fn from_pieces(pieces: [u64; 12]) -> Self {
         let white = pieces[0] | pieces[1] | pieces[2] | pieces[3] | pieces[4] | pieces[5];
         let black = pieces[6] | pieces[7] | pieces[8] | pieces[9] | pieces[10] | pieces[11];
+        let mut mailbox = [None; 64];
+        for (index, &bitboard) in pieces.iter().enumerate() {
+            for sq in BitIter(bitboard) {
+                mailbox[sq] = Some(PIECES[index]);
+            }
+        }
+
         Self {
             pieces,
             white,
             black,
             all: white | black,
+            mailbox,
         }
     }
 
-    fn update_occupancy(&mut self) {
-        self.white = self.pieces[0]
-            | self.pieces[1]
-            | self.pieces[2]
-            | self.pieces[3]
-            | self.pieces[4]
-            | self.pieces[5];
-        self.black = self.pieces[6]
-            | self.pieces[7]
-            | self.pieces[8]
-            | self.pieces[9]
-            | self.pieces[10]
-            | self.pieces[11];
-        self.all = self.white | self.black;
-    }
-
+    #[inline(always)]
     pub fn get_piece(&self, square: &Square) -> Option<Piece> {
         debug_assert!(square.0.count_ones() == 1);
+        self.mailbox[square.index()]
+    }
 
-        let sq = square.0;
-
-        for (index, &bitboard) in self.pieces.iter().enumerate() {
-            if bitboard & sq != 0 {
-                return match index {
-                    WHITE_PAWN => Some(Piece(PieceType::Pawn, Color::White)),
-                    WHITE_KNIGHT => Some(Piece(PieceType::Knight, Color::White)),
-                    WHITE_BISHOP => Some(Piece(PieceType::Bishop, Color::White)),
-                    WHITE_ROOK => Some(Piece(PieceType::Rook, Color::White)),
-                    WHITE_QUEEN => Some(Piece(PieceType::Queen, Color::White)),
-                    WHITE_KING => Some(Piece(PieceType::King, Color::White)),
-                    BLACK_PAWN => Some(Piece(PieceType::Pawn, Color::Black)),
-                    BLACK_KNIGHT => Some(Piece(PieceType::Knight, Color::Black)),
-                    BLACK_BISHOP => Some(Piece(PieceType::Bishop, Color::Black)),
-                    BLACK_ROOK => Some(Piece(PieceType::Rook, Color::Black)),
-                    BLACK_QUEEN => Some(Piece(PieceType::Queen, Color::Black)),
-                    BLACK_KING => Some(Piece(PieceType::King, Color::Black)),
-                    _ => unreachable!(),
-                };
-            }
-        }
-
-        None
+    // Same as get_piece, by square index
+    #[inline(always)]
+    pub fn piece_at(&self, sq: usize) -> Option<Piece> {
+        self.mailbox[sq]
     }
 
     pub fn set_piece(&mut self, square: Square, piece: Piece) {
@@ -91,22 +86,29 @@ impl Bitboard {
 
         self.remove_piece(&square);
 
-        let idx = piece_to_index(piece);
-        self.pieces[idx] |= square.0;
-        self.update_occupancy();
+        self.pieces[piece_to_index(piece)] |= square.0;
+        match piece.1 {
+            Color::White => self.white |= square.0,
+            Color::Black => self.black |= square.0,
+        }
+        self.all |= square.0;
+        self.mailbox[square.index()] = Some(piece);
     }
 
     pub fn remove_piece(&mut self, square: &Square) {
         debug_assert!(square.0.count_ones() == 1);
 
-        let prev_piece = match self.get_piece(square) {
+        let prev_piece = match self.mailbox[square.index()].take() {
             Some(piece) => piece,
             None => return,
         };
 
-        let idx = piece_to_index(prev_piece);
-        self.pieces[idx] &= !square.0;
-        self.update_occupancy();
+        self.pieces[piece_to_index(prev_piece)] &= !square.0;
+        match prev_piece.1 {
+            Color::White => self.white &= !square.0,
+            Color::Black => self.black &= !square.0,
+        }
+        self.all &= !square.0;
     }
 
     pub fn is_square_empty(&self, square: &Square) -> bool {
@@ -167,19 +169,9 @@ impl std::fmt::Display for Bitboard {
             write!(f, "{} ", rank + 1)?;
 
             for file in 0..8 {
-                let sq_bit = 1u64 << (file + rank * 8);
-                let mut piece_found = false;
-
-                for (index, &bitboard) in self.pieces.iter().enumerate() {
-                    if bitboard & sq_bit != 0 {
-                        write!(f, "{} ", chessboard[index])?;
-                        piece_found = true;
-                        break;
-                    }
-                }
-
-                if !piece_found {
-                    write!(f, ". ")?;
+                match self.mailbox[file + rank * 8] {
+                    Some(piece) => write!(f, "{} ", chessboard[piece_to_index(piece)])?,
+                    None => write!(f, ". ")?,
                 }
             }
 
@@ -234,6 +226,38 @@ mod tests {
         assert_eq!(bb.white, e2.0);
         assert_eq!(bb.black, e7.0);
         assert_eq!(bb.all, e2.0 | e7.0);
+
+        bb.remove_piece(&e2);
+        assert_eq!(bb.white, 0);
+        assert_eq!(bb.all, e7.0);
+    }
+
+    #[test]
+    fn test_mailbox_in_sync() {
+        let mut bb = Bitboard::new();
+        let d4 = Square::from(3, 3).unwrap();
+
+        bb.set_piece(d4, Piece(PieceType::Knight, Color::White));
+        assert_eq!(bb.piece_at(27), Some(Piece(PieceType::Knight, Color::White)));
+
+        // Replacing a piece clears it from its old bitboard
+        bb.set_piece(d4, Piece(PieceType::Queen, Color::Black));
+        assert_eq!(bb.get_piece(&d4), Some(Piece(PieceType::Queen, Color::Black)));
+        assert_eq!(bb.piece_bb(PieceType::Knight, Color::White), 0);
+        assert_eq!(bb.white, 0);
+        assert_eq!(bb.black, d4.0);
+
+        bb.remove_piece(&d4);
+        assert_eq!(bb.piece_at(27), None);
+        assert_eq!(bb, Bitboard::new());
+
+        let mut pieces = [0; 12];
+        pieces[WHITE_KING] = 1 << 4;
+        pieces[BLACK_ROOK] = 1 << 63;
+        let bb = Bitboard::from_pieces(pieces);
+        assert_eq!(bb.piece_at(4), Some(Piece(PieceType::King, Color::White)));
+        assert_eq!(bb.piece_at(63), Some(Piece(PieceType::Rook, Color::Black)));
+        assert_eq!(bb.mailbox.iter().flatten().count(), 2);
     }
 
     #[test]
@@ -1,6 +1,9 @@
-use crate::error::{Error, Result};
+use crate::error::{Error, FenError, Result};
+use crate::movegen::{generate_moves, is_square_attacked, Move};
+use crate::tables::{DARK_SQUARES, LIGHT_SQUARES, RANK_1, RANK_8};
+use crate::zobrist::{CASTLING_KEYS, EN_PASSANT_KEYS, PIECE_KEYS, SIDE_KEY};
 
-use super::bitboard::Bitboard;
+use super::bitboard::{piece_to_index, Bitboard, BitIter};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PieceType {
@@ -12,6 +15,43 @@ pub enum PieceType {
     King,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    // The side to move is mated
+    Checkmate,
+    Stalemate,
+    FiftyMoveRule,
+    Repetition,
+    InsufficientMaterial,
+}
+
+impl GameStatus {
+    #[inline(always)]
+    pub fn is_game_over(self) -> bool {
+        self != GameStatus::Ongoing
+    }
+
+    #[inline(always)]
+    pub fn is_draw(self) -> bool {
+        !matches!(self, GameStatus::Ongoing | GameStatus::Checkmate)
+    }
+}
+
+impl std::fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            GameStatus::Ongoing => "ongoing",
+            GameStatus::Checkmate => "checkmate",
+            GameStatus::Stalemate => "stalemate",
+            GameStatus::FiftyMoveRule => "draw by fifty-move rule",
+            GameStatus::Repetition => "draw by threefold repetition",
+            GameStatus::InsufficientMaterial => "draw by insufficient material",
+        };
+        write!(f, "{}", text)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
     White,
@@ -57,6 +97,21 @@ impl Piece {
             _ => None,
         }
     }
+
+    pub const fn to_char(self) -> char {
+        let c = match self.0 {
+            PieceType::Pawn => 'p',
+            PieceType::Knight => 'n',
+            PieceType::Bishop => 'b',
+            PieceType::Rook => 'r',
+            PieceType::Queen => 'q',
+            PieceType::King => 'k',
+        };
+        match self.1 {
+            Color::White => c.to_ascii_uppercase(),
+            Color::Black => c,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -118,12 +173,20 @@ impl Square {
     }
 }
 
+#[inline(always)]
+fn fen_error(error: FenError) -> Error {
+    Error::InvalidFen(error)
+}
+
 // Castling rights bit flags
 pub const WK_CASTLE: u8 = 0b0001;
 pub const WQ_CASTLE: u8 = 0b0010;
 pub const BK_CASTLE: u8 = 0b0100;
 pub const BQ_CASTLE: u8 = 0b1000;
 
+// Rook squares of the four rights in bit order, h1 a1 h8 a8 outside Chess960
+pub const STANDARD_CASTLING_ROOKS: [u8; 4] = [7, 0, 63, 56];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CastlingRights(pub u8);
 
@@ -137,94 +200,383 @@ impl CastlingRights {
     pub fn remove(&mut self, right: u8) {
         self.0 &= !right;
     }
+
+    #[inline(always)]
+    pub fn for_color(color: Color) -> u8 {
+        match color {
+            Color::White => WK_CASTLE | WQ_CASTLE,
+            Color::Black => BK_CASTLE | BQ_CASTLE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FenOptions {
+    // Write the en passant square after every double push, not only when
+    // the capture is actually legal
+    pub always_en_passant: bool,
+    // Shredder-FEN: castling rights as rook files (HAha) instead of KQkq
+    pub shredder: bool,
 }
 
+// Everything make_move_mut destroys that can't be recomputed from the move
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Undo {
+    pub captured: Option<Piece>,
+    pub castling: CastlingRights,
+    pub en_passant: Option<Square>,
+    pub halfmove: u16,
+    pub hash: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct Board {
     pub bitboard: Bitboard,
     pub turn: Color,
     pub en_passant: Option<Square>,
     pub castling: CastlingRights,
+    // Start square of the rook for each castling right, indexed by the
+    // right's bit. Only meaningful while the right is held.
+    pub castling_rooks: [u8; 4],
     pub halfmove: u16,
     pub fullmove: u64,
+    pub hash: u64,
 }
 
 impl Board {
+    // Strict parser: all six fields are required and the position must be one
+    // that can occur in a game
     pub fn from_fen(fen: &str) -> Result<Self> {
-        let mut parts = fen.split_whitespace();
+        Self::parse_fen(fen, false)
+    }
 
-        let piece_placement = parts.next().ok_or(Error::InvalidFen)?;
-        let turn = parts.next().ok_or(Error::InvalidFen)?;
-        let castling = parts.next().ok_or(Error::InvalidFen)?;
-        let en_passant = parts.next().ok_or(Error::InvalidFen)?;
-        let halfmove = parts.next().ok_or(Error::InvalidFen)?;
-        let fullmove = parts.next().ok_or(Error::InvalidFen)?;
+    // Also accepts EPD style FENs without the move counters, which then
+    // default to 0 and 1
+    pub fn from_fen_lenient(fen: &str) -> Result<Self> {
+        Self::parse_fen(fen, true)
+    }
 
-        let mut boardstate = Board {
+    fn parse_fen(fen: &str, lenient: bool) -> Result<Self> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let counters_omitted = lenient && (4..6).contains(&fields.len());
+        if fields.len() != 6 && !counters_omitted {
+            return Err(fen_error(FenError::FieldCount(fields.len())));
+        }
+
+        let mut board = Board {
             bitboard: Bitboard::new(),
-            turn: match turn {
-                "w" => Color::White,
-                "b" => Color::Black,
-                _ => return Err(Error::InvalidTurn),
-            },
-            en_passant: match en_passant {
-                "-" => None,
-                _ => Some(Square::from_algebraic(en_passant)?),
-            },
-            castling: match castling {
-                "-" => CastlingRights(0b0000),
-                _ => {
-                    let mut rights = CastlingRights(0);
-                    for c in castling.chars() {
-                        if let Some(right) = match c {
-                            'K' => Some(WK_CASTLE),
-                            'Q' => Some(WQ_CASTLE),
-                            'k' => Some(BK_CASTLE),
-                            'q' => Some(BQ_CASTLE),
-                            _ => None,
-                        } {
-                            rights.0 |= right;
-                        }
-                    }
-                    rights
-                }
-            },
-            halfmove: halfmove.parse().map_err(Error::ParseError)?,
-            fullmove: fullmove.parse().map_err(Error::ParseError)?,
+            turn: Color::White,
+            en_passant: None,
+            castling: CastlingRights(0),
+            castling_rooks: STANDARD_CASTLING_ROOKS,
+            halfmove: 0,
+            fullmove: 1,
+            hash: 0,
         };
 
-        let mut rank = 7;
-        let mut file = 0;
-        for c in piece_placement.chars() {
-            match c {
-                '/' => {
-                    rank -= 1;
-                    file = 0;
+        board.parse_placement(fields[0])?;
+        board.turn = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            side => return Err(fen_error(FenError::InvalidSideToMove(side.to_string()))),
+        };
+        board.parse_castling(fields[2])?;
+        board.en_passant = board.parse_en_passant(fields[3])?;
+
+        if let Some(halfmove) = fields.get(4) {
+            board.halfmove = halfmove
+                .parse()
+                .map_err(|_| fen_error(FenError::InvalidHalfmove(halfmove.to_string())))?;
+        }
+        if let Some(fullmove) = fields.get(5) {
+            board.fullmove = fullmove
+                .parse()
+                .map_err(|_| fen_error(FenError::InvalidFullmove(fullmove.to_string())))?;
+        }
+
+        let them = board.turn.opposite();
+        if is_square_attacked(&board, board.king_square(them), board.turn) {
+            return Err(fen_error(FenError::OpponentInCheck));
+        }
+
+        board.hash = board.compute_hash();
+
+        Ok(board)
+    }
+
+    fn parse_placement(&mut self, placement: &str) -> Result<()> {
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(fen_error(FenError::RankCount(ranks.len())));
+        }
+
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i as u8;
+            let mut squares = 0;
+
+            for c in rank_str.chars() {
+                if let Some(empty) = c.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                    squares += empty;
+                    continue;
                 }
-                '1'..='8' => file += c as u8 - b'0',
-                _ => {
-                    if let Some(piece) = Piece::from_char(c) {
-                        let sq = Square::from(file, rank)?;
-                        boardstate.set_piece(sq, piece);
-                        file += 1;
-                    } else {
-                        return Err(Error::InvalidFen);
+
+                let piece = Piece::from_char(c).ok_or(fen_error(FenError::InvalidPiece {
+                    rank: rank + 1,
+                    piece: c,
+                }))?;
+                if squares < 8 {
+                    self.set_piece(Square::from(squares as u8, rank)?, piece);
+                }
+                squares += 1;
+            }
+
+            if squares != 8 {
+                return Err(fen_error(FenError::RankLength {
+                    rank: rank + 1,
+                    squares,
+                }));
+            }
+        }
+
+        for color in [Color::White, Color::Black] {
+            let count = self.pieces(PieceType::King, color).count_ones();
+            if count != 1 {
+                return Err(fen_error(FenError::KingCount { color, count }));
+            }
+        }
+
+        let pawns =
+            self.pieces(PieceType::Pawn, Color::White) | self.pieces(PieceType::Pawn, Color::Black);
+        if let Some(sq) = BitIter(pawns & (RANK_1 | RANK_8)).next() {
+            let square = Square::from_index(sq).to_algebraic();
+            return Err(fen_error(FenError::PawnOnBackRank(square)));
+        }
+
+        Ok(())
+    }
+
+    // Accepts KQkq, where X-FEN takes the outermost rook on that side of the
+    // king, and Shredder-FEN rook files such as HAha. Each right needs the
+    // king on its back rank and a rook on the named square.
+    fn parse_castling(&mut self, field: &str) -> Result<()> {
+        if field == "-" {
+            return Ok(());
+        }
+
+        for c in field.chars() {
+            let color = if c.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let back_rank = match color {
+                Color::White => 0,
+                Color::Black => 56,
+            };
+            let without_pieces = || fen_error(FenError::CastlingWithoutPieces(c));
+
+            let king_sq = self.king_square(color);
+            if king_sq / 8 != back_rank / 8 {
+                return Err(without_pieces());
+            }
+            let king_file = king_sq % 8;
+            let rook = Some(Piece(PieceType::Rook, color));
+
+            let rook_file = match c.to_ascii_lowercase() {
+                'k' => (king_file + 1..8)
+                    .rev()
+                    .find(|file| self.piece_at(back_rank + file) == rook),
+                'q' => (0..king_file).find(|file| self.piece_at(back_rank + file) == rook),
+                file @ 'a'..='h' => Some(file as usize - 'a' as usize)
+                    .filter(|&file| file != king_file && self.piece_at(back_rank + file) == rook),
+                _ => return Err(fen_error(FenError::InvalidCastling(c))),
+            }
+            .ok_or_else(without_pieces)?;
+
+            let right = match (color, rook_file > king_file) {
+                (Color::White, true) => WK_CASTLE,
+                (Color::White, false) => WQ_CASTLE,
+                (Color::Black, true) => BK_CASTLE,
+                (Color::Black, false) => BQ_CASTLE,
+            };
+            if self.castling.has(right) {
+                return Err(fen_error(FenError::InvalidCastling(c)));
+            }
+            self.castling.0 |= right;
+            self.castling_rooks[right.trailing_zeros() as usize] = (back_rank + rook_file) as u8;
+        }
+
+        Ok(())
+    }
+
+    // The square must lie behind an enemy pawn that could just have made a
+    // double push, with both squares it passed over empty
+    fn parse_en_passant(&self, field: &str) -> Result<Option<Square>> {
+        if field == "-" {
+            return Ok(None);
+        }
+
+        let invalid = || fen_error(FenError::InvalidEnPassant(field.to_string()));
+        let square = Square::from_algebraic(field).map_err(|_| invalid())?;
+        let sq = square.index();
+
+        let (rank, pawn_sq, start_sq, them) = match self.turn {
+            Color::White => (5, sq.wrapping_sub(8), sq + 8, Color::Black),
+            Color::Black => (2, sq + 8, sq.wrapping_sub(8), Color::White),
+        };
+        if square.rank() != rank {
+            return Err(invalid());
+        }
+
+        if self.piece_at(pawn_sq) != Some(Piece(PieceType::Pawn, them))
+            || self.piece_at(sq).is_some()
+            || self.piece_at(start_sq).is_some()
+        {
+            return Err(fen_error(FenError::ImpossibleEnPassant(field.to_string())));
+        }
+
+        Ok(Some(square))
+    }
+
+    pub fn to_fen(&self) -> String {
+        self.to_fen_with(FenOptions::default())
+    }
+
+    pub fn to_fen_with(&self, options: FenOptions) -> String {
+        let mut fen = String::with_capacity(90);
+
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_at(rank * 8 + file) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push((b'0' + empty) as char);
+                            empty = 0;
+                        }
+                        fen.push(piece.to_char());
                     }
+                    None => empty += 1,
                 }
             }
+            if empty > 0 {
+                fen.push((b'0' + empty) as char);
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
         }
 
-        Ok(boardstate)
+        fen.push(' ');
+        fen.push(match self.turn {
+            Color::White => 'w',
+            Color::Black => 'b',
+        });
+
+        fen.push(' ');
+        if self.castling.0 == 0 {
+            fen.push('-');
+        }
+        for (right, c) in [
+            (WK_CASTLE, 'K'),
+            (WQ_CASTLE, 'Q'),
+            (BK_CASTLE, 'k'),
+            (BQ_CASTLE, 'q'),
+        ] {
+            if self.castling.has(right) {
+                fen.push(self.castling_char(right, c, options.shredder));
+            }
+        }
+
+        fen.push(' ');
+        match self.en_passant {
+            Some(ep) if options.always_en_passant || self.has_en_passant_capture() => {
+                fen.push_str(&ep.to_algebraic())
+            }
+            _ => fen.push('-'),
+        }
+
+        fen.push_str(&format!(" {} {}", self.halfmove, self.fullmove));
+        fen
     }
 
-    pub fn make_move(self, mv: crate::movegen::Move) -> Board {
-        use crate::movegen::{
-            FLAG_CAPTURE, FLAG_DOUBLE_PUSH, FLAG_EP_CAPTURE, FLAG_KING_CASTLE, FLAG_PROMO_B,
-            FLAG_PROMO_CAPTURE_B, FLAG_PROMO_CAPTURE_N, FLAG_PROMO_CAPTURE_Q, FLAG_PROMO_CAPTURE_R,
-            FLAG_PROMO_N, FLAG_PROMO_Q, FLAG_PROMO_R, FLAG_QUEEN_CASTLE,
+    // X-FEN keeps KQkq for the outermost rook on each side and only names
+    // the file of an inner rook
+    fn castling_char(&self, right: u8, standard: char, shredder: bool) -> char {
+        let rook_sq = self.castling_rooks[right.trailing_zeros() as usize] as usize;
+        let color = if standard.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
         };
+        let king_sq = self.king_square(color);
+        let rank_start = king_sq / 8 * 8;
+        let mut outside = if rook_sq > king_sq {
+            rook_sq + 1..rank_start + 8
+        } else {
+            rank_start..rook_sq
+        };
+        let outermost = outside.all(|sq| self.piece_at(sq) != Some(Piece(PieceType::Rook, color)));
+
+        if outermost && !shredder {
+            return standard;
+        }
+        let file = (b'a' + (rook_sq % 8) as u8) as char;
+        match color {
+            Color::White => file.to_ascii_uppercase(),
+            Color::Black => file,
+        }
+    }
 
+    fn has_en_passant_capture(&self) -> bool {
+        use crate::movegen::FLAG_EP_CAPTURE;
+
+        self.en_passant.is_some()
+            && generate_moves(self)
+                .iter()
+                .any(|mv| mv.flags() == FLAG_EP_CAPTURE)
+    }
+
+    // Zobrist key computed from scratch
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = 0u64;
+
+        for (index, &pieces) in self.bitboard.pieces.iter().enumerate() {
+            for sq in BitIter(pieces) {
+                hash ^= PIECE_KEYS[index][sq];
+            }
+        }
+
+        if self.turn == Color::Black {
+            hash ^= SIDE_KEY;
+        }
+
+        hash ^= CASTLING_KEYS[self.castling.0 as usize];
+
+        if let Some(ep) = self.en_passant {
+            hash ^= EN_PASSANT_KEYS[ep.file() as usize];
+        }
+
+        hash
+    }
+
+    // Copy-make, the board itself is left untouched
+    pub fn make_move(self, mv: Move) -> Board {
         let mut new_board = self;
+        new_board.make_move_mut(mv);
+        new_board
+    }
+
+    // Plays the move in place, the returned record lets unmake_move restore
+    // the position exactly
+    pub fn make_move_mut(&mut self, mv: Move) -> Undo {
+        use crate::movegen::{
+            FLAG_CAPTURE, FLAG_DOUBLE_PUSH, FLAG_EP_CAPTURE, FLAG_PROMO_B, FLAG_PROMO_CAPTURE_B,
+            FLAG_PROMO_CAPTURE_N, FLAG_PROMO_CAPTURE_Q, FLAG_PROMO_CAPTURE_R, FLAG_PROMO_N,
+            FLAG_PROMO_Q, FLAG_PROMO_R,
+        };
+
+        let us = self.turn;
         let from = mv.from();
         let to = mv.to();
         let flags = mv.flags();
@@ -232,23 +584,42 @@ impl Board {
         let from_sq = Square::from_index(from);
         let to_sq = Square::from_index(to);
 
-        let piece = new_board.get_piece(&from_sq).unwrap();
+        let piece = self.get_piece(&from_sq).unwrap();
+        let mut undo = Undo {
+            captured: None,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove: self.halfmove,
+            hash: self.hash,
+        };
 
         // Remove piece from source
-        new_board.remove_piece(&from_sq);
+        self.remove_piece(&from_sq);
+
+        // Castling is encoded as the king taking its own rook, which keeps
+        // the move unambiguous when the king doesn't move or lands on the
+        // rook's square in Chess960
+        if mv.is_castle() {
+            let (king_to, rook_to) = mv.castling_squares();
+            self.remove_piece(&to_sq);
+            self.set_piece(Square::from_index(king_to), piece);
+            self.set_piece(Square::from_index(rook_to), Piece(PieceType::Rook, us));
+        }
 
         // Handle captures (remove captured piece)
         if flags == FLAG_CAPTURE || flags >= FLAG_PROMO_CAPTURE_N {
-            new_board.remove_piece(&to_sq);
+            undo.captured = self.get_piece(&to_sq);
+            self.remove_piece(&to_sq);
         }
 
         // Handle en passant capture
         if flags == FLAG_EP_CAPTURE {
-            let captured_sq = match self.turn {
+            let captured_sq = match us {
                 Color::White => Square::from_index(to - 8),
                 Color::Black => Square::from_index(to + 8),
             };
-            new_board.remove_piece(&captured_sq);
+            self.remove_piece(&captured_sq);
+            undo.captured = Some(Piece(PieceType::Pawn, us.opposite()));
         }
 
         // Place piece at destination (possibly promoted)
@@ -259,82 +630,116 @@ impl Board {
             FLAG_PROMO_Q | FLAG_PROMO_CAPTURE_Q => Piece(PieceType::Queen, piece.1),
             _ => piece,
         };
-        new_board.set_piece(to_sq, dest_piece);
-
-        // Handle castling - move the rook
-        if flags == FLAG_KING_CASTLE {
-            match self.turn {
-                Color::White => {
-                    new_board.remove_piece(&Square::from_index(7)); // h1
-                    new_board.set_piece(Square::from_index(5), Piece(PieceType::Rook, Color::White));
-                }
-                Color::Black => {
-                    new_board.remove_piece(&Square::from_index(63)); // h8
-                    new_board.set_piece(Square::from_index(61), Piece(PieceType::Rook, Color::Black));
-                }
-            }
-        }
-        if flags == FLAG_QUEEN_CASTLE {
-            match self.turn {
-                Color::White => {
-                    new_board.remove_piece(&Square::from_index(0)); // a1
-                    new_board.set_piece(Square::from_index(3), Piece(PieceType::Rook, Color::White));
-                }
-                Color::Black => {
-                    new_board.remove_piece(&Square::from_index(56)); // a8
-                    new_board.set_piece(Square::from_index(59), Piece(PieceType::Rook, Color::Black));
-                }
-            }
+        if !mv.is_castle() {
+            self.set_piece(to_sq, dest_piece);
         }
 
         // Update castling rights
+        self.hash ^= CASTLING_KEYS[self.castling.0 as usize];
         if piece.0 == PieceType::King {
-            match self.turn {
-                Color::White => new_board.castling.0 &= !0b0011,
-                Color::Black => new_board.castling.0 &= !0b1100,
-            }
+            self.castling.remove(CastlingRights::for_color(us));
         }
         // Rook moves or captures
-        if from == 0 || to == 0 {
-            new_board.castling.0 &= !0b0010; // White queenside
-        }
-        if from == 7 || to == 7 {
-            new_board.castling.0 &= !0b0001; // White kingside
-        }
-        if from == 56 || to == 56 {
-            new_board.castling.0 &= !0b1000; // Black queenside
-        }
-        if from == 63 || to == 63 {
-            new_board.castling.0 &= !0b0100; // Black kingside
+        for (i, &rook_sq) in self.castling_rooks.iter().enumerate() {
+            if from == rook_sq as usize || to == rook_sq as usize {
+                self.castling.remove(1 << i);
+            }
         }
+        self.hash ^= CASTLING_KEYS[self.castling.0 as usize];
 
         // Update en passant square
-        new_board.en_passant = if flags == FLAG_DOUBLE_PUSH {
-            match self.turn {
+        if let Some(ep) = self.en_passant {
+            self.hash ^= EN_PASSANT_KEYS[ep.file() as usize];
+        }
+        self.en_passant = if flags == FLAG_DOUBLE_PUSH {
+            match us {
                 Color::White => Some(Square::from_index(from + 8)),
                 Color::Black => Some(Square::from_index(from - 8)),
             }
         } else {
             None
         };
+        if let Some(ep) = self.en_passant {
+            self.hash ^= EN_PASSANT_KEYS[ep.file() as usize];
+        }
 
         // Switch turn
-        new_board.turn = self.turn.opposite();
+        self.turn = us.opposite();
+        self.hash ^= SIDE_KEY;
 
         // Update move counters
-        if self.turn == Color::Black {
-            new_board.fullmove += 1;
+        if us == Color::Black {
+            self.fullmove += 1;
         }
         if piece.0 == PieceType::Pawn || flags == FLAG_CAPTURE || flags == FLAG_EP_CAPTURE {
-            new_board.halfmove = 0;
+            self.halfmove = 0;
         } else {
-            new_board.halfmove += 1;
+            self.halfmove += 1;
         }
 
-        new_board
+        debug_assert_eq!(self.hash, self.compute_hash());
+
+        undo
+    }
+
+    // Takes back `mv`, which must be the last move played with make_move_mut
+    pub fn unmake_move(&mut self, mv: Move, undo: Undo) {
+        use crate::movegen::FLAG_EP_CAPTURE;
+
+        let us = self.turn.opposite();
+        let from_sq = Square::from_index(mv.from());
+        let to_sq = Square::from_index(mv.to());
+
+        // The bitboards are restored directly, the hash is restored from the record
+        if mv.is_castle() {
+            let (king_to, rook_to) = mv.castling_squares();
+            self.bitboard.remove_piece(&Square::from_index(king_to));
+            self.bitboard.remove_piece(&Square::from_index(rook_to));
+            self.bitboard.set_piece(from_sq, Piece(PieceType::King, us));
+            self.bitboard.set_piece(to_sq, Piece(PieceType::Rook, us));
+            self.restore(us, undo);
+            return;
+        }
+
+        let moved = self.get_piece(&to_sq).unwrap();
+        let piece = if mv.is_promotion() {
+            Piece(PieceType::Pawn, us)
+        } else {
+            moved
+        };
+        self.bitboard.remove_piece(&to_sq);
+        self.bitboard.set_piece(from_sq, piece);
+
+        if let Some(captured) = undo.captured {
+            let captured_sq = match (mv.flags(), us) {
+                (FLAG_EP_CAPTURE, Color::White) => Square::from_index(mv.to() - 8),
+                (FLAG_EP_CAPTURE, Color::Black) => Square::from_index(mv.to() + 8),
+                _ => to_sq,
+            };
+            self.bitboard.set_piece(captured_sq, captured);
+        }
+
+        self.restore(us, undo);
+    }
+
+    fn restore(&mut self, us: Color, undo: Undo) {
+        self.turn = us;
+        if us == Color::Black {
+            self.fullmove -= 1;
+        }
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove = undo.halfmove;
+        self.hash = undo.hash;
+
+        debug_assert_eq!(self.hash, self.compute_hash());
     }
 
     pub fn set_piece(&mut self, square: Square, piece: Piece) {
+        if let Some(prev) = self.get_piece(&square) {
+            self.hash ^= PIECE_KEYS[piece_to_index(prev)][square.index()];
+        }
+        self.hash ^= PIECE_KEYS[piece_to_index(piece)][square.index()];
         self.bitboard.set_piece(square, piece);
     }
 
@@ -342,7 +747,15 @@ impl Board {
         self.bitboard.get_piece(square)
     }
 
+    #[inline(always)]
+    pub fn piece_at(&self, sq: usize) -> Option<Piece> {
+        self.bitboard.piece_at(sq)
+    }
+
     pub fn remove_piece(&mut self, square: &Square) {
+        if let Some(prev) = self.get_piece(square) {
+            self.hash ^= PIECE_KEYS[piece_to_index(prev)][square.index()];
+        }
         self.bitboard.remove_piece(square);
     }
 
@@ -383,6 +796,79 @@ impl Board {
         self.pieces(piece_type, color)
     }
 
+    // Either side may claim a draw after 50 moves without a capture or pawn move
+    #[inline(always)]
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove >= 100
+    }
+
+    // How often this position occurred before. `history` holds the Zobrist keys
+    // of the earlier game positions, oldest first, without the current one.
+    pub fn repetitions(&self, history: &[u64]) -> usize {
+        // Nothing before the last capture or pawn move can repeat
+        let window = (self.halfmove as usize).min(history.len());
+        history[history.len() - window..]
+            .iter()
+            .rev()
+            .skip(1)
+            .step_by(2)
+            .filter(|&&key| key == self.hash)
+            .count()
+    }
+
+    pub fn in_check(&self) -> bool {
+        let us = self.side_to_move();
+        is_square_attacked(self, self.king_square(us), us.opposite())
+    }
+
+    // Neither side can mate with any sequence of moves: bare kings, a single
+    // minor piece, or bishops that all stand on squares of one colour
+    pub fn is_insufficient_material(&self) -> bool {
+        let heavy = self.pieces(PieceType::Pawn, Color::White)
+            | self.pieces(PieceType::Pawn, Color::Black)
+            | self.pieces(PieceType::Rook, Color::White)
+            | self.pieces(PieceType::Rook, Color::Black)
+            | self.pieces(PieceType::Queen, Color::White)
+            | self.pieces(PieceType::Queen, Color::Black);
+        if heavy != 0 {
+            return false;
+        }
+
+        let knights = self.pieces(PieceType::Knight, Color::White)
+            | self.pieces(PieceType::Knight, Color::Black);
+        let bishops = self.pieces(PieceType::Bishop, Color::White)
+            | self.pieces(PieceType::Bishop, Color::Black);
+
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+
+        knights == 0 && (bishops & DARK_SQUARES == 0 || bishops & LIGHT_SQUARES == 0)
+    }
+
+    // Result of the game in this position. `history` holds the keys of the
+    // earlier game positions, oldest first, as for `repetitions`.
+    pub fn status(&self, history: &[u64]) -> GameStatus {
+        // Mate and stalemate take precedence over the fifty-move rule
+        if generate_moves(self).is_empty() {
+            return if self.in_check() {
+                GameStatus::Checkmate
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+
+        if self.is_fifty_move_draw() {
+            GameStatus::FiftyMoveRule
+        } else if self.repetitions(history) >= 2 {
+            GameStatus::Repetition
+        } else if self.is_insufficient_material() {
+            GameStatus::InsufficientMaterial
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
     pub fn display(&self) {
         println!("{}", self.bitboard);
     }
@@ -405,13 +891,57 @@ impl std::default::Default for Board {
             0x1000000000000000, // Black king
         ];
 
-        Self {
+        let mut board = Self {
             bitboard: Bitboard::from_pieces(pieces),
             turn: Color::White,
             castling: CastlingRights(0b1111),
+            castling_rooks: STANDARD_CASTLING_ROOKS,
             en_passant: None,
             halfmove: 0,
             fullmove: 1,
-        }
+            hash: 0,
+        };
+        board.hash = board.compute_hash();
+        board
+    }
+}
+
+// Rook squares of rights that are no longer held don't affect the position
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        let held_rooks = |board: &Board| {
+            let mut rooks = board.castling_rooks;
+            for (i, rook) in rooks.iter_mut().enumerate() {
+                if !board.castling.has(1 << i) {
+                    *rook = 0;
+                }
+            }
+            rooks
+        };
+
+        self.bitboard == other.bitboard
+            && self.turn == other.turn
+            && self.en_passant == other.en_passant
+            && self.castling == other.castling
+            && held_rooks(self) == held_rooks(other)
+            && self.halfmove == other.halfmove
+            && self.fullmove == other.fullmove
+            && self.hash == other.hash
+    }
+}
+
+impl Eq for Board {}
+
+impl std::fmt::Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_fen())
+    }
+}
+
+impl std::str::FromStr for Board {
+    type Err = Error;
+
+    fn from_str(fen: &str) -> Result<Self> {
+        Board::from_fen(fen)
     }
 }
@@ -0,0 +1,349 @@
+use std::fs;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::board::Board;
+use crate::error::{Error, Result};
+use crate::movegen::Move;
+use crate::san::{parse_san, to_san};
+use crate::search::{mate_in, SearchLimits, SearchResult, Searcher};
+
+// One line of an EPD file: a position without move counters followed by
+// `opcode operand...;` operations
+#[derive(Debug, Clone)]
+pub struct Epd {
+    pub board: Board,
+    pub id: Option<String>,
+    // c0, the primary comment
+    pub comment: Option<String>,
+    pub best_moves: Vec<Move>,
+    pub avoid_moves: Vec<Move>,
+    // dm, mate in this many full moves
+    pub direct_mate: Option<u32>,
+    // Every operation in the order given, including the ones above
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    pub fn parse(line: &str) -> Result<Self> {
+        let mut rest = line.trim();
+        let mut fields = Vec::with_capacity(4);
+        for _ in 0..4 {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+
+        let mut epd = Self {
+            board: Board::from_fen_lenient(&fields.join(" "))?,
+            id: None,
+            comment: None,
+            best_moves: Vec::new(),
+            avoid_moves: Vec::new(),
+            direct_mate: None,
+            operations: parse_operations(rest)?,
+        };
+
+        for (opcode, operands) in &epd.operations {
+            match opcode.as_str() {
+                "id" => epd.id = Some(single_operand(opcode, operands)?.to_string()),
+                "c0" => epd.comment = Some(single_operand(opcode, operands)?.to_string()),
+                "bm" | "am" => {
+                    let moves = operands
+                        .iter()
+                        .map(|operand| parse_move(&epd.board, operand))
+                        .collect::<Result<Vec<Move>>>()
+                        .map_err(|e| operand_error(opcode, e.to_string()))?;
+                    if opcode == "bm" {
+                        epd.best_moves = moves;
+                    } else {
+                        epd.avoid_moves = moves;
+                    }
+                }
+                "dm" => epd.direct_mate = Some(number_operand(opcode, operands)?),
+                "hmvc" => epd.board.halfmove = number_operand(opcode, operands)?,
+                "fmvn" => epd.board.fullmove = number_operand(opcode, operands)?,
+                _ => {}
+            }
+        }
+
+        Ok(epd)
+    }
+
+    // All operands of an opcode, None if it isn't present
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    // Whether a search result passes every bm, am and dm requirement
+    pub fn is_solved(&self, result: &SearchResult) -> bool {
+        let Some(mv) = result.best_move else {
+            return false;
+        };
+
+        (self.best_moves.is_empty() || self.best_moves.contains(&mv))
+            && !self.avoid_moves.contains(&mv)
+            && self.direct_mate.is_none_or(|moves| {
+                mate_in(result.score).is_some_and(|mate| mate > 0 && mate as u32 <= moves)
+            })
+    }
+
+    // The requirements as they would appear in the file, with moves in SAN
+    pub fn expectation(&self) -> String {
+        let mut parts = Vec::new();
+        for (opcode, moves) in [("bm", &self.best_moves), ("am", &self.avoid_moves)] {
+            if !moves.is_empty() {
+                let moves: Vec<String> = moves.iter().map(|&mv| to_san(&self.board, mv)).collect();
+                parts.push(format!("{} {}", opcode, moves.join(" ")));
+            }
+        }
+        if let Some(moves) = self.direct_mate {
+            parts.push(format!("dm {}", moves));
+        }
+
+        parts.join(", ")
+    }
+}
+
+impl FromStr for Epd {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+#[inline(always)]
+fn epd_error(message: String) -> Error {
+    Error::InvalidEpd(message)
+}
+
+fn operand_error(opcode: &str, reason: String) -> Error {
+    epd_error(format!("{}: {}", opcode, reason))
+}
+
+fn single_operand<'a>(opcode: &str, operands: &'a [String]) -> Result<&'a str> {
+    match operands {
+        [operand] => Ok(operand),
+        _ => Err(operand_error(
+            opcode,
+            format!("expected 1 operand, found {}", operands.len()),
+        )),
+    }
+}
+
+fn number_operand<T: FromStr>(opcode: &str, operands: &[String]) -> Result<T> {
+    let operand = single_operand(opcode, operands)?;
+    operand
+        .parse()
+        .map_err(|_| operand_error(opcode, format!("'{}' is not a number", operand)))
+}
+
+// Suites mostly give SAN, some use coordinate notation
+fn parse_move(board: &Board, s: &str) -> Result<Move> {
+    Move::from_uci(board, s).or_else(|_| parse_san(board, s))
+}
+
+// Splits `bm Nf3 Ne5; id "name; with semicolon";` into opcodes and operands,
+// the final semicolon may be missing
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>> {
+    let mut operations = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+
+    for c in text.chars() {
+        if quoted {
+            if c == '"' {
+                words.push(std::mem::take(&mut word));
+                quoted = false;
+            } else {
+                word.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                finish_word(&mut word, &mut words);
+                quoted = true;
+            }
+            ';' => {
+                finish_word(&mut word, &mut words);
+                if !words.is_empty() {
+                    let opcode = words.remove(0);
+                    operations.push((opcode, std::mem::take(&mut words)));
+                }
+            }
+            _ if c.is_whitespace() => finish_word(&mut word, &mut words),
+            _ => word.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(epd_error("unterminated string operand".to_string()));
+    }
+    finish_word(&mut word, &mut words);
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        operations.push((opcode, words));
+    }
+
+    Ok(operations)
+}
+
+fn finish_word(word: &mut String, words: &mut Vec<String>) {
+    if !word.is_empty() {
+        words.push(std::mem::take(word));
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SuiteSummary {
+    pub solved: usize,
+    pub failed: usize,
+    pub invalid: usize,
+}
+
+// Searches every position of an EPD file for `movetime` and prints whether
+// the engine found the expected move
+pub fn run_suite(path: &str, movetime: Duration) -> Result<SuiteSummary> {
+    let text = fs::read_to_string(path)?;
+    let mut searcher = Searcher::new();
+    let limits = SearchLimits {
+        movetime: Some(movetime),
+        ..SearchLimits::default()
+    };
+    let mut summary = SuiteSummary::default();
+    let start = Instant::now();
+
+    let lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+
+    for (number, line) in lines {
+        let epd = match Epd::parse(line) {
+            Ok(epd) => epd,
+            Err(e) => {
+                println!("line {}: {}", number + 1, e);
+                summary.invalid += 1;
+                continue;
+            }
+        };
+
+        // Every position is searched from scratch
+        searcher.tt().clear();
+        searcher.clear_history_tables();
+        let result = searcher.search(&epd.board, &limits);
+        let solved = epd.is_solved(&result);
+        if solved {
+            summary.solved += 1;
+        } else {
+            summary.failed += 1;
+        }
+
+        let played = result
+            .best_move
+            .map_or_else(|| "(none)".to_string(), |mv| to_san(&epd.board, mv));
+        let id = epd
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("line {}", number + 1));
+        println!(
+            "{:<16} {:<6} {:<8} {}",
+            id,
+            if solved { "solved" } else { "failed" },
+            played,
+            epd.expectation()
+        );
+    }
+
+    let total = summary.solved + summary.failed;
+    println!();
+    println!(
+        "solved {}/{}, failed {}, invalid {}, {:.1}s",
+        summary.solved,
+        total,
+        summary.failed,
+        summary.invalid,
+        start.elapsed().as_secs_f64()
+    );
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MATE;
+
+    const WAC_001: &str = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - \
+                           bm Qg6; id \"WAC.001\";";
+
+    fn result(epd: &Epd, uci: &str, score: i32) -> SearchResult {
+        SearchResult {
+            best_move: Some(Move::from_uci(&epd.board, uci).unwrap()),
+            score,
+            ..SearchResult::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_operations() {
+        let epd = Epd::parse(WAC_001).unwrap();
+        assert_eq!(epd.id.as_deref(), Some("WAC.001"));
+        assert_eq!(epd.best_moves.len(), 1);
+        assert_eq!(epd.best_moves[0].to_uci(), "g3g6");
+        assert_eq!(epd.expectation(), "bm Qg6");
+
+        let epd: Epd = "4k3/8/8/8/8/8/8/R3K3 w Q - am O-O-O Kd1; bm Ra8+ e1f2; \
+                        c0 \"a; quoted comment\"; hmvc 12; fmvn 40; xx 1 2"
+            .parse()
+            .unwrap();
+        assert_eq!(epd.avoid_moves[0].to_uci(), "e1c1");
+        assert_eq!(epd.avoid_moves.len(), 2);
+        assert_eq!(epd.best_moves.len(), 2);
+        assert_eq!(epd.comment.as_deref(), Some("a; quoted comment"));
+        assert_eq!((epd.board.halfmove, epd.board.fullmove), (12, 40));
+        assert_eq!(
+            epd.operation("xx"),
+            Some(&["1".to_string(), "2".to_string()][..])
+        );
+        assert_eq!(epd.operation("id"), None);
+        assert_eq!(epd.expectation(), "bm Ra8+ Kf2, am O-O-O Kd1");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let invalid = |line: &str| match Epd::parse(line) {
+            Err(Error::InvalidEpd(message)) => message,
+            other => panic!("{} parsed as {:?}", line, other),
+        };
+
+        assert!(invalid("4k3/8/8/8/8/8/8/R3K3 w Q - bm Nf3;").starts_with("bm: Invalid move"));
+        assert!(invalid("4k3/8/8/8/8/8/8/R3K3 w Q - dm two;").starts_with("dm: 'two'"));
+        assert!(invalid("4k3/8/8/8/8/8/8/R3K3 w Q - id a b;").starts_with("id: expected 1"));
+        assert!(invalid("4k3/8/8/8/8/8/8/R3K3 w Q - c0 \"open").contains("unterminated"));
+        assert!(matches!(
+            Epd::parse("4k3/8/8/8 w - - bm Kd1;"),
+            Err(Error::InvalidFen(_))
+        ));
+    }
+
+    #[test]
+    fn test_is_solved() {
+        let epd = Epd::parse(WAC_001).unwrap();
+        assert!(epd.is_solved(&result(&epd, "g3g6", 0)));
+        assert!(!epd.is_solved(&result(&epd, "f6h7", 0)));
+        assert!(!epd.is_solved(&SearchResult::default()));
+
+        let epd = Epd::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - am Kf1; dm 1;").unwrap();
+        assert!(epd.is_solved(&result(&epd, "a1a8", MATE - 1)));
+        assert!(!epd.is_solved(&result(&epd, "a1a7", 0)));
+        assert!(!epd.is_solved(&result(&epd, "g1f1", MATE - 1)));
+    }
+}
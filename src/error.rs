@@ -1,13 +1,92 @@
 use core::fmt;
 
+use crate::board::Color;
+
+// What is wrong with a FEN string, ranks are numbered 1-8 as on the board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    FieldCount(usize),
+    RankCount(usize),
+    RankLength { rank: u8, squares: u32 },
+    InvalidPiece { rank: u8, piece: char },
+    KingCount { color: Color, count: u32 },
+    PawnOnBackRank(String),
+    InvalidSideToMove(String),
+    InvalidCastling(char),
+    CastlingWithoutPieces(char),
+    InvalidEnPassant(String),
+    ImpossibleEnPassant(String),
+    InvalidHalfmove(String),
+    InvalidFullmove(String),
+    OpponentInCheck,
+}
+
+// Why a move string was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    Syntax,
+    NoPiece,
+    Illegal,
+    Ambiguous,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::Syntax => write!(f, "not a move in UCI or SAN notation"),
+            MoveError::NoPiece => write!(f, "no piece of the side to move on that square"),
+            MoveError::Illegal => write!(f, "illegal in this position"),
+            MoveError::Ambiguous => write!(f, "matches more than one legal move"),
+        }
+    }
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::FieldCount(count) => write!(f, "expected 6 fields, found {}", count),
+            FenError::RankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::RankLength { rank, squares } => {
+                write!(f, "rank {} describes {} squares instead of 8", rank, squares)
+            }
+            FenError::InvalidPiece { rank, piece } => {
+                write!(f, "unknown piece '{}' on rank {}", piece, rank)
+            }
+            FenError::KingCount { color, count } => {
+                write!(f, "{:?} has {} kings instead of 1", color, count)
+            }
+            FenError::PawnOnBackRank(square) => write!(f, "pawn on back rank square {}", square),
+            FenError::InvalidSideToMove(side) => {
+                write!(f, "side to move must be w or b, found '{}'", side)
+            }
+            FenError::InvalidCastling(c) => write!(f, "unexpected castling right '{}'", c),
+            FenError::CastlingWithoutPieces(c) => {
+                write!(f, "castling right '{}' without king and rook in place", c)
+            }
+            FenError::InvalidEnPassant(square) => {
+                write!(f, "'{}' is not an en passant square", square)
+            }
+            FenError::ImpossibleEnPassant(square) => {
+                write!(f, "no double pawn push can have passed {}", square)
+            }
+            FenError::InvalidHalfmove(value) => write!(f, "invalid halfmove clock '{}'", value),
+            FenError::InvalidFullmove(value) => write!(f, "invalid fullmove number '{}'", value),
+            FenError::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     InvalidSquare(u8, u8),
     InvalidAlgebraicNotation,
-    InvalidFen,
-    InvalidTurn,
+    InvalidFen(FenError),
     ParseError(std::num::ParseIntError),
-    InvalidMove(String),
+    InvalidMove(String, MoveError),
+    InvalidCommand(String),
+    InvalidOption(String),
+    InvalidPgn(String),
+    InvalidEpd(String),
     IoError(std::io::Error),
 }
 
@@ -21,17 +100,26 @@ impl fmt::Display for Error {
             Error::InvalidAlgebraicNotation => {
                 write!(f, "Invalid algebraic notation")
             }
-            Error::InvalidFen => {
-                write!(f, "Invalid FEN")
-            }
-            Error::InvalidTurn => {
-                write!(f, "Error parsing turn-to-move")
+            Error::InvalidFen(e) => {
+                write!(f, "Invalid FEN: {}", e)
             }
             Error::ParseError(e) => {
                 write!(f, "Parse error: {}", e)
             }
-            Error::InvalidMove(mv) => {
-                write!(f, "Invalid move: {}", mv)
+            Error::InvalidMove(mv, reason) => {
+                write!(f, "Invalid move: {} ({})", mv, reason)
+            }
+            Error::InvalidCommand(cmd) => {
+                write!(f, "Invalid command: {}", cmd)
+            }
+            Error::InvalidOption(opt) => {
+                write!(f, "Invalid option: {}", opt)
+            }
+            Error::InvalidPgn(msg) => {
+                write!(f, "Invalid PGN: {}", msg)
+            }
+            Error::InvalidEpd(msg) => {
+                write!(f, "Invalid EPD: {}", msg)
             }
             Error::IoError(e) => {
                 write!(f, "IO error: {}", e)
@@ -1,23 +1,33 @@
 use crate::board::{Board, Color, PieceType};
 
+// Material values
+#[inline(always)]
+pub const fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 20000,
+    }
+}
+
 pub fn evaluate_position(board: &Board) -> i32 {
     let mut score = 0;
 
-    // Material values
-    let material_values = [
-        (PieceType::Pawn, 100),
-        (PieceType::Knight, 320),
-        (PieceType::Bishop, 330),
-        (PieceType::Rook, 500),
-        (PieceType::Queen, 900),
-        (PieceType::King, 20000),
-    ];
-
-    for (piece_type, value) in &material_values {
+    for piece_type in [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+        PieceType::King,
+    ] {
         for color in [Color::White, Color::Black] {
-            let pieces = board.get_pieces(*piece_type, color);
+            let pieces = board.get_pieces(piece_type, color);
             let piece_count = pieces.count_ones();
-            let material_score = piece_count as i32 * value;
+            let material_score = piece_count as i32 * piece_value(piece_type);
 
             if color == Color::White {
                 score += material_score;
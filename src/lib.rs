@@ -1,8 +1,19 @@
 pub mod bitboard;
 pub mod board;
+pub mod epd;
 pub mod error;
 pub mod eval;
 pub mod magic;
 pub mod movegen;
+pub mod movepick;
+pub mod options;
+pub mod perft;
+pub mod pgn;
+pub mod san;
+pub mod search;
+pub mod see;
 pub mod tables;
+pub mod timeman;
+pub mod tt;
 pub mod uci;
+pub mod zobrist;
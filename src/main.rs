@@ -1,9 +1,13 @@
-use brainybishop::board::Color;
+use brainybishop::board::{Board, Color};
+use brainybishop::epd::run_suite;
 use brainybishop::error::Result;
+use brainybishop::perft::{print_divide, PerftOptions};
 use brainybishop::uci::{run_interactive_mode, UciEngine};
 use std::env;
+use std::time::Duration;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const DEFAULT_EPD_MOVETIME_MS: u64 = 1000;
 
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
@@ -20,6 +24,26 @@ fn main() -> Result<()> {
             "black" | "b" => {
                 run_interactive_mode(Color::Black)?;
             }
+            "epd" => match parse_epd_args(&args[2..]) {
+                Ok((path, movetime)) => {
+                    run_suite(path, movetime)?;
+                }
+                Err(message) => {
+                    eprintln!("{}", message);
+                    print_help();
+                    std::process::exit(1);
+                }
+            },
+            "perft" => match parse_perft_args(&args[2..]) {
+                Ok((depth, board, options)) => {
+                    print_divide(&board, depth, options);
+                }
+                Err(message) => {
+                    eprintln!("{}", message);
+                    print_help();
+                    std::process::exit(1);
+                }
+            },
             "--help" | "-h" => {
                 print_help();
             }
@@ -42,4 +66,72 @@ fn print_help() {
     println!("Usage:");
     println!("  brainybishop [white|black]  - Play as white or black (default: white)");
     println!("  brainybishop uci            - UCI protocol mode");
+    println!("  brainybishop epd <file> [--movetime ms]");
+    println!(
+        "                              - Run a test suite, {}ms per position by default",
+        DEFAULT_EPD_MOVETIME_MS
+    );
+    println!("  brainybishop perft <depth> [fen] [--hash mb] [--threads n] [--chess960]");
+    println!("                              - Count leaf nodes below each move");
+}
+
+// `<depth> [fen] [--hash mb] [--threads n] [--chess960]`, the FEN defaults to the
+// start position
+fn parse_perft_args(args: &[String]) -> std::result::Result<(u32, Board, PerftOptions), String> {
+    let depth = args.first().ok_or("Missing perft depth")?;
+    let depth = depth
+        .parse()
+        .map_err(|_| format!("Invalid depth: {}", depth))?;
+    let mut options = PerftOptions::default();
+    let mut fen = Vec::new();
+    let mut args = args[1..].iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--hash" | "--threads" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                let n = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {}: {}", arg, value))?;
+                if arg == "--hash" {
+                    options.hash_mb = n;
+                } else {
+                    options.threads = n;
+                }
+            }
+            "--chess960" => options.chess960 = true,
+            _ => fen.push(arg.as_str()),
+        }
+    }
+
+    let board = if fen.is_empty() {
+        Board::default()
+    } else {
+        Board::from_fen_lenient(&fen.join(" ")).map_err(|e| e.to_string())?
+    };
+
+    Ok((depth, board, options))
+}
+
+// `<file> [--movetime ms]` in any order
+fn parse_epd_args(args: &[String]) -> std::result::Result<(&str, Duration), String> {
+    let mut path = None;
+    let mut movetime = Duration::from_millis(DEFAULT_EPD_MOVETIME_MS);
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--movetime" => {
+                let value = args.next().ok_or("--movetime needs a value")?;
+                let ms = value
+                    .parse()
+                    .map_err(|_| format!("Invalid movetime: {}", value))?;
+                movetime = Duration::from_millis(ms);
+            }
+            _ if path.is_none() => path = Some(arg.as_str()),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    path.map(|path| (path, movetime)).ok_or_else(|| "Missing EPD file".to_string())
 }
@@ -1,5 +1,6 @@
 use crate::bitboard::BitIter;
-use crate::board::{Board, Color, PieceType, BK_CASTLE, BQ_CASTLE, WK_CASTLE, WQ_CASTLE};
+use crate::board::{Board, Color, PieceType, Square, BK_CASTLE, BQ_CASTLE, WK_CASTLE, WQ_CASTLE};
+use crate::error::{Error, MoveError, Result};
 use crate::magic::{bishop_attacks, queen_attacks, rook_attacks};
 use crate::tables::{BETWEEN, KING_ATTACKS, KNIGHT_ATTACKS, LINE, PAWN_ATTACKS, RANK_2, RANK_7};
 
@@ -31,6 +32,16 @@ impl Move {
         Move((from as u16) | ((to as u16) << 6) | (flags << 12))
     }
 
+    #[inline(always)]
+    pub const fn from_bits(bits: u16) -> Self {
+        Move(bits)
+    }
+
+    #[inline(always)]
+    pub const fn to_bits(self) -> u16 {
+        self.0
+    }
+
     #[inline(always)]
     pub const fn from(self) -> usize {
         (self.0 & 0x3F) as usize
@@ -63,6 +74,17 @@ impl Move {
         f == FLAG_KING_CASTLE || f == FLAG_QUEEN_CASTLE
     }
 
+    // Where the king and the rook end up. Castles are stored as the king
+    // capturing its own rook, so to() is the rook's start square.
+    #[inline(always)]
+    pub const fn castling_squares(self) -> (usize, usize) {
+        let rank = self.from() / 8 * 8;
+        match self.flags() {
+            FLAG_KING_CASTLE => (rank + 6, rank + 5),
+            _ => (rank + 2, rank + 3),
+        }
+    }
+
     #[inline(always)]
     pub fn promotion_piece(self) -> Option<PieceType> {
         match self.flags() {
@@ -74,11 +96,63 @@ impl Move {
         }
     }
 
+    // Parses coordinate notation such as e2e4 or e7e8q, the flags come from
+    // the board and the move has to be legal in it. Castling is accepted both
+    // as the king's destination (e1g1) and as king takes rook (e1h1).
+    pub fn from_uci(board: &Board, s: &str) -> Result<Move> {
+        let error = |reason| Error::InvalidMove(s.to_string(), reason);
+
+        if !s.is_ascii() || !(4..=5).contains(&s.len()) {
+            return Err(error(MoveError::Syntax));
+        }
+        let from = Square::from_algebraic(&s[0..2]).map_err(|_| error(MoveError::Syntax))?;
+        let to = Square::from_algebraic(&s[2..4]).map_err(|_| error(MoveError::Syntax))?;
+        let promotion = match s.as_bytes().get(4) {
+            None => None,
+            Some(b'n') => Some(PieceType::Knight),
+            Some(b'b') => Some(PieceType::Bishop),
+            Some(b'r') => Some(PieceType::Rook),
+            Some(b'q') => Some(PieceType::Queen),
+            Some(_) => return Err(error(MoveError::Syntax)),
+        };
+
+        match board.piece_at(from.index()) {
+            Some(piece) if piece.1 == board.side_to_move() => {}
+            _ => return Err(error(MoveError::NoPiece)),
+        }
+
+        let moves = generate_moves(board);
+        let exact = moves.iter().copied().find(|mv| {
+            mv.from() == from.index() && mv.to() == to.index() && mv.promotion_piece() == promotion
+        });
+        let castle = || {
+            moves.iter().copied().find(|mv| {
+                mv.is_castle()
+                    && mv.from() == from.index()
+                    && mv.castling_squares().0 == to.index()
+                    && promotion.is_none()
+            })
+        };
+
+        exact.or_else(castle).ok_or(error(MoveError::Illegal))
+    }
+
     pub fn to_uci(self) -> String {
+        self.to_uci_with(false)
+    }
+
+    // Chess960 GUIs expect castling as king takes rook, others expect the
+    // square the king lands on
+    pub fn to_uci_with(self, chess960: bool) -> String {
+        let to = if self.is_castle() && !chess960 {
+            self.castling_squares().0
+        } else {
+            self.to()
+        };
         let from_file = (self.from() % 8) as u8 + b'a';
         let from_rank = (self.from() / 8) as u8 + b'1';
-        let to_file = (self.to() % 8) as u8 + b'a';
-        let to_rank = (self.to() / 8) as u8 + b'1';
+        let to_file = (to % 8) as u8 + b'a';
+        let to_rank = (to / 8) as u8 + b'1';
 
         let mut s = String::with_capacity(5);
         s.push(from_file as char);
@@ -145,6 +219,16 @@ impl MoveList {
     pub fn iter(&self) -> impl Iterator<Item = &Move> {
         self.moves[..self.len].iter()
     }
+
+    #[inline(always)]
+    pub fn as_slice(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+
+    #[inline(always)]
+    pub fn as_mut_slice(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
 }
 
 impl Default for MoveList {
@@ -234,25 +318,29 @@ fn compute_attacks(board: &Board, color: Color, exclude_king_sq: usize) -> u64 {
 }
 
 fn attackers_of(board: &Board, sq: usize, color: Color) -> u64 {
-    let occ = board.all_occupancy();
-    let mut attackers = 0u64;
-
-    let pawn_attacks = PAWN_ATTACKS[color.opposite().index()][sq];
-    attackers |= pawn_attacks & board.pieces(PieceType::Pawn, color);
-
-    attackers |= KNIGHT_ATTACKS[sq] & board.pieces(PieceType::Knight, color);
-
-    let bishop_rays = bishop_attacks(sq, occ);
-    attackers |=
-        bishop_rays & (board.pieces(PieceType::Bishop, color) | board.pieces(PieceType::Queen, color));
-
-    let rook_rays = rook_attacks(sq, occ);
-    attackers |=
-        rook_rays & (board.pieces(PieceType::Rook, color) | board.pieces(PieceType::Queen, color));
+    attackers_to(board, sq, board.all_occupancy()) & board.occupancy(color)
+}
 
-    attackers |= KING_ATTACKS[sq] & board.pieces(PieceType::King, color);
+// Pieces of both colors attacking `sq` when only the pieces in `occ` block
+// the sliders. Pieces missing from `occ` are still included, callers that
+// remove pieces have to mask them out.
+pub fn attackers_to(board: &Board, sq: usize, occ: u64) -> u64 {
+    let pieces = |piece_type| {
+        board.pieces(piece_type, Color::White) | board.pieces(piece_type, Color::Black)
+    };
+    let queens = pieces(PieceType::Queen);
+
+    (PAWN_ATTACKS[Color::Black.index()][sq] & board.pieces(PieceType::Pawn, Color::White))
+        | (PAWN_ATTACKS[Color::White.index()][sq] & board.pieces(PieceType::Pawn, Color::Black))
+        | (KNIGHT_ATTACKS[sq] & pieces(PieceType::Knight))
+        | (bishop_attacks(sq, occ) & (pieces(PieceType::Bishop) | queens))
+        | (rook_attacks(sq, occ) & (pieces(PieceType::Rook) | queens))
+        | (KING_ATTACKS[sq] & pieces(PieceType::King))
+}
 
-    attackers
+#[inline(always)]
+pub fn is_square_attacked(board: &Board, sq: usize, by_color: Color) -> bool {
+    attackers_of(board, sq, by_color) != 0
 }
 
 fn compute_pins(board: &Board, king_sq: usize, us: Color, pin_masks: &mut [u64; 64], occ: u64) {
@@ -294,28 +382,126 @@ fn compute_pins(board: &Board, king_sq: usize, us: Color, pin_masks: &mut [u64;
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenType {
+    All,
+    // Captures (including en passant and capture-promotions) and queen promotions
+    Captures,
+    // Everything else: quiet moves, castling and quiet underpromotions
+    Quiets,
+}
+
 pub fn generate_moves(board: &Board) -> MoveList {
-    let mut moves = MoveList::new();
     let info = AttackInfo::new(board);
+    generate(board, &info, GenType::All)
+}
+
+pub fn generate_captures(board: &Board) -> MoveList {
+    let info = AttackInfo::new(board);
+    generate(board, &info, GenType::Captures)
+}
+
+// Captures and Quiets together are exactly All, so a caller that already has
+// the attack info can generate them one after the other
+pub fn generate(board: &Board, info: &AttackInfo, gen: GenType) -> MoveList {
+    let mut moves = MoveList::new();
+    let us = board.turn;
+    let targets = match gen {
+        GenType::All => !board.occupancy(us),
+        GenType::Captures => board.occupancy(us.opposite()),
+        GenType::Quiets => !board.all_occupancy(),
+    };
 
     // Double check: only king moves are legal
     if info.in_double_check() {
-        generate_king_moves(board, &info, &mut moves);
+        generate_king_moves(board, info, targets, &mut moves);
         return moves;
     }
 
-    generate_pawn_moves(board, &info, &mut moves);
-    generate_knight_moves(board, &info, &mut moves);
-    generate_bishop_moves(board, &info, &mut moves);
-    generate_rook_moves(board, &info, &mut moves);
-    generate_queen_moves(board, &info, &mut moves);
-    generate_king_moves(board, &info, &mut moves);
-    generate_castling_moves(board, &info, &mut moves);
+    generate_pawn_moves(board, info, gen, &mut moves);
+    generate_knight_moves(board, info, targets, &mut moves);
+    generate_bishop_moves(board, info, targets, &mut moves);
+    generate_rook_moves(board, info, targets, &mut moves);
+    generate_queen_moves(board, info, targets, &mut moves);
+    generate_king_moves(board, info, targets, &mut moves);
+    if gen != GenType::Captures {
+        generate_castling_moves(board, info, &mut moves);
+    }
 
     moves
 }
 
-fn generate_pawn_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList) {
+// Whether a move from somewhere else, such as the hash table or a killer
+// slot, is one of the legal moves here, without generating all of them
+pub fn is_legal(board: &Board, info: &AttackInfo, mv: Move) -> bool {
+    let us = board.turn;
+    let from = mv.from();
+    let to = mv.to();
+    let Some(piece) = board.piece_at(from).filter(|piece| piece.1 == us) else {
+        return false;
+    };
+
+    if mv.is_castle() {
+        let mut castles = MoveList::new();
+        generate_castling_moves(board, info, &mut castles);
+        return castles.iter().any(|&castle| castle == mv);
+    }
+
+    if piece.0 == PieceType::Pawn {
+        let mut pawn_moves = MoveList::new();
+        let pawn = 1u64 << from;
+        let their_pieces = board.occupancy(us.opposite());
+        let empty = !board.all_occupancy();
+        if !info.in_double_check() {
+            match us {
+                Color::White => gen_white_pawn_moves(
+                    board,
+                    info,
+                    GenType::All,
+                    &mut pawn_moves,
+                    pawn,
+                    their_pieces,
+                    empty,
+                ),
+                Color::Black => gen_black_pawn_moves(
+                    board,
+                    info,
+                    GenType::All,
+                    &mut pawn_moves,
+                    pawn,
+                    their_pieces,
+                    empty,
+                ),
+            }
+        }
+        return pawn_moves.iter().any(|&pawn_move| pawn_move == mv);
+    }
+
+    // Everything else is a plain move or capture onto a square it attacks
+    let to_bb = 1u64 << to;
+    let flag = if board.occupancy(us.opposite()) & to_bb != 0 {
+        FLAG_CAPTURE
+    } else {
+        FLAG_QUIET
+    };
+    if mv.flags() != flag || board.occupancy(us) & to_bb != 0 {
+        return false;
+    }
+
+    let occ = board.all_occupancy();
+    let attacks = match piece.0 {
+        PieceType::King => return KING_ATTACKS[from] & !info.opponent_attacks & to_bb != 0,
+        PieceType::Knight => KNIGHT_ATTACKS[from],
+        PieceType::Bishop => bishop_attacks(from, occ),
+        PieceType::Rook => rook_attacks(from, occ),
+        PieceType::Queen => queen_attacks(from, occ),
+        PieceType::Pawn => unreachable!(),
+    };
+
+    attacks & info.check_mask & info.pin_masks[from] & to_bb != 0
+}
+
+fn generate_pawn_moves(board: &Board, info: &AttackInfo, gen: GenType, moves: &mut MoveList) {
     let us = board.turn;
     let them = us.opposite();
     let our_pawns = board.pieces(PieceType::Pawn, us);
@@ -323,14 +509,19 @@ fn generate_pawn_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList) {
     let empty = !board.all_occupancy();
 
     match us {
-        Color::White => gen_white_pawn_moves(board, info, moves, our_pawns, their_pieces, empty),
-        Color::Black => gen_black_pawn_moves(board, info, moves, our_pawns, their_pieces, empty),
+        Color::White => {
+            gen_white_pawn_moves(board, info, gen, moves, our_pawns, their_pieces, empty)
+        }
+        Color::Black => {
+            gen_black_pawn_moves(board, info, gen, moves, our_pawns, their_pieces, empty)
+        }
     }
 }
 
 fn gen_white_pawn_moves(
     board: &Board,
     info: &AttackInfo,
+    gen: GenType,
     moves: &mut MoveList,
     pawns: u64,
     enemies: u64,
@@ -348,14 +539,14 @@ fn gen_white_pawn_moves(
             let to = from + 8;
             if (1u64 << to) & check_mask & pin_mask != 0 {
                 if to >= 56 {
-                    add_promotions(moves, from, to, false);
-                } else {
+                    add_promotions(moves, from, to, false, gen);
+                } else if gen != GenType::Captures {
                     moves.push(Move::new(from, to, FLAG_QUIET));
                 }
             }
 
             // Double push (only if single push was to empty square)
-            if from_bb & RANK_2 != 0 {
+            if gen != GenType::Captures && from_bb & RANK_2 != 0 {
                 let double = (from_bb << 16) & empty;
                 if double != 0 {
                     let to = from + 16;
@@ -366,6 +557,10 @@ fn gen_white_pawn_moves(
             }
         }
 
+        if gen == GenType::Quiets {
+            continue;
+        }
+
         // Captures
         let attacks = PAWN_ATTACKS[0][from] & pin_mask & check_mask;
 
@@ -373,7 +568,7 @@ fn gen_white_pawn_moves(
         let left_capture = attacks & enemies;
         for to in BitIter(left_capture) {
             if to >= 56 {
-                add_promotions(moves, from, to, true);
+                add_promotions(moves, from, to, true, gen);
             } else {
                 moves.push(Move::new(from, to, FLAG_CAPTURE));
             }
@@ -382,9 +577,9 @@ fn gen_white_pawn_moves(
         // En passant
         if let Some(ep_sq) = board.en_passant {
             let ep_bb = ep_sq.0;
-            if attacks & ep_bb != 0 {
+            if PAWN_ATTACKS[0][from] & pin_mask & ep_bb != 0 {
                 let to = ep_sq.index();
-                if is_ep_legal(board, from, to, board.turn) {
+                if is_ep_legal(board, info, from, to, board.turn) {
                     moves.push(Move::new(from, to, FLAG_EP_CAPTURE));
                 }
             }
@@ -395,6 +590,7 @@ fn gen_white_pawn_moves(
 fn gen_black_pawn_moves(
     board: &Board,
     info: &AttackInfo,
+    gen: GenType,
     moves: &mut MoveList,
     pawns: u64,
     enemies: u64,
@@ -412,14 +608,14 @@ fn gen_black_pawn_moves(
             let to = from - 8;
             if (1u64 << to) & check_mask & pin_mask != 0 {
                 if to < 8 {
-                    add_promotions(moves, from, to, false);
-                } else {
+                    add_promotions(moves, from, to, false, gen);
+                } else if gen != GenType::Captures {
                     moves.push(Move::new(from, to, FLAG_QUIET));
                 }
             }
 
             // Double push
-            if from_bb & RANK_7 != 0 {
+            if gen != GenType::Captures && from_bb & RANK_7 != 0 {
                 let double = (from_bb >> 16) & empty;
                 if double != 0 {
                     let to = from - 16;
@@ -430,13 +626,17 @@ fn gen_black_pawn_moves(
             }
         }
 
+        if gen == GenType::Quiets {
+            continue;
+        }
+
         // Captures
         let attacks = PAWN_ATTACKS[1][from] & pin_mask & check_mask;
 
         let captures = attacks & enemies;
         for to in BitIter(captures) {
             if to < 8 {
-                add_promotions(moves, from, to, true);
+                add_promotions(moves, from, to, true, gen);
             } else {
                 moves.push(Move::new(from, to, FLAG_CAPTURE));
             }
@@ -447,7 +647,7 @@ fn gen_black_pawn_moves(
             let ep_bb = ep_sq.0;
             if PAWN_ATTACKS[1][from] & pin_mask & ep_bb != 0 {
                 let to = ep_sq.index();
-                if is_ep_legal(board, from, to, board.turn) {
+                if is_ep_legal(board, info, from, to, board.turn) {
                     moves.push(Move::new(from, to, FLAG_EP_CAPTURE));
                 }
             }
@@ -455,47 +655,50 @@ fn gen_black_pawn_moves(
     }
 }
 
-fn add_promotions(moves: &mut MoveList, from: usize, to: usize, is_capture: bool) {
+fn add_promotions(moves: &mut MoveList, from: usize, to: usize, is_capture: bool, gen: GenType) {
     if is_capture {
         moves.push(Move::new(from, to, FLAG_PROMO_CAPTURE_Q));
         moves.push(Move::new(from, to, FLAG_PROMO_CAPTURE_R));
         moves.push(Move::new(from, to, FLAG_PROMO_CAPTURE_B));
         moves.push(Move::new(from, to, FLAG_PROMO_CAPTURE_N));
     } else {
-        moves.push(Move::new(from, to, FLAG_PROMO_Q));
-        moves.push(Move::new(from, to, FLAG_PROMO_R));
-        moves.push(Move::new(from, to, FLAG_PROMO_B));
-        moves.push(Move::new(from, to, FLAG_PROMO_N));
+        if gen != GenType::Quiets {
+            moves.push(Move::new(from, to, FLAG_PROMO_Q));
+        }
+        if gen != GenType::Captures {
+            moves.push(Move::new(from, to, FLAG_PROMO_R));
+            moves.push(Move::new(from, to, FLAG_PROMO_B));
+            moves.push(Move::new(from, to, FLAG_PROMO_N));
+        }
     }
 }
 
-fn is_ep_legal(board: &Board, from: usize, to: usize, us: Color) -> bool {
-    // Check if en passant exposes king to horizontal attack
-    let king_sq = board.king_square(us);
-    let king_rank = king_sq / 8;
-    let pawn_rank = from / 8;
-
-    // En passant can only expose horizontal attacks if king is on same rank
-    if king_rank != pawn_rank {
-        return true;
-    }
-
+fn is_ep_legal(board: &Board, info: &AttackInfo, from: usize, to: usize, us: Color) -> bool {
     let captured_sq = match us {
         Color::White => to - 8,
         Color::Black => to + 8,
     };
 
+    // In check the capture has to either take the checking pawn or block
+    if info.check_mask & ((1u64 << to) | (1u64 << captured_sq)) == 0 {
+        return false;
+    }
+
+    // Two pawns leave the board at once, which can uncover a slider the pin
+    // masks know nothing about, most often along the rank of both pawns
+    let king_sq = board.king_square(us);
     let them = us.opposite();
     let occ = board.all_occupancy() & !(1u64 << from) & !(1u64 << captured_sq) | (1u64 << to);
-    let rook_queen = board.pieces(PieceType::Rook, them) | board.pieces(PieceType::Queen, them);
+    let queens = board.pieces(PieceType::Queen, them);
+    let rook_queen = board.pieces(PieceType::Rook, them) | queens;
+    let bishop_queen = board.pieces(PieceType::Bishop, them) | queens;
 
-    (rook_attacks(king_sq, occ) & rook_queen) == 0
+    rook_attacks(king_sq, occ) & rook_queen == 0 && bishop_attacks(king_sq, occ) & bishop_queen == 0
 }
 
-fn generate_knight_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList) {
+fn generate_knight_moves(board: &Board, info: &AttackInfo, targets: u64, moves: &mut MoveList) {
     let us = board.turn;
     let our_knights = board.pieces(PieceType::Knight, us);
-    let our_pieces = board.occupancy(us);
     let their_pieces = board.occupancy(us.opposite());
 
     for from in BitIter(our_knights) {
@@ -504,7 +707,7 @@ fn generate_knight_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList)
             continue;
         }
 
-        let attacks = KNIGHT_ATTACKS[from] & !our_pieces & info.check_mask;
+        let attacks = KNIGHT_ATTACKS[from] & targets & info.check_mask;
 
         for to in BitIter(attacks) {
             let flag = if their_pieces & (1 << to) != 0 {
@@ -517,16 +720,15 @@ fn generate_knight_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList)
     }
 }
 
-fn generate_bishop_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList) {
+fn generate_bishop_moves(board: &Board, info: &AttackInfo, targets: u64, moves: &mut MoveList) {
     let us = board.turn;
     let bishops = board.pieces(PieceType::Bishop, us);
-    let our_pieces = board.occupancy(us);
     let their_pieces = board.occupancy(us.opposite());
     let occ = board.all_occupancy();
 
     for from in BitIter(bishops) {
         let pin_mask = info.pin_masks[from];
-        let attacks = bishop_attacks(from, occ) & !our_pieces & info.check_mask & pin_mask;
+        let attacks = bishop_attacks(from, occ) & targets & info.check_mask & pin_mask;
 
         for to in BitIter(attacks) {
             let flag = if their_pieces & (1 << to) != 0 {
@@ -539,16 +741,15 @@ fn generate_bishop_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList)
     }
 }
 
-fn generate_rook_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList) {
+fn generate_rook_moves(board: &Board, info: &AttackInfo, targets: u64, moves: &mut MoveList) {
     let us = board.turn;
     let rooks = board.pieces(PieceType::Rook, us);
-    let our_pieces = board.occupancy(us);
     let their_pieces = board.occupancy(us.opposite());
     let occ = board.all_occupancy();
 
     for from in BitIter(rooks) {
         let pin_mask = info.pin_masks[from];
-        let attacks = rook_attacks(from, occ) & !our_pieces & info.check_mask & pin_mask;
+        let attacks = rook_attacks(from, occ) & targets & info.check_mask & pin_mask;
 
         for to in BitIter(attacks) {
             let flag = if their_pieces & (1 << to) != 0 {
@@ -561,16 +762,15 @@ fn generate_rook_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList) {
     }
 }
 
-fn generate_queen_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList) {
+fn generate_queen_moves(board: &Board, info: &AttackInfo, targets: u64, moves: &mut MoveList) {
     let us = board.turn;
     let queens = board.pieces(PieceType::Queen, us);
-    let our_pieces = board.occupancy(us);
     let their_pieces = board.occupancy(us.opposite());
     let occ = board.all_occupancy();
 
     for from in BitIter(queens) {
         let pin_mask = info.pin_masks[from];
-        let attacks = queen_attacks(from, occ) & !our_pieces & info.check_mask & pin_mask;
+        let attacks = queen_attacks(from, occ) & targets & info.check_mask & pin_mask;
 
         for to in BitIter(attacks) {
             let flag = if their_pieces & (1 << to) != 0 {
@@ -583,13 +783,12 @@ fn generate_queen_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList)
     }
 }
 
-fn generate_king_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList) {
+fn generate_king_moves(board: &Board, info: &AttackInfo, targets: u64, moves: &mut MoveList) {
     let us = board.turn;
     let king_sq = board.king_square(us);
-    let our_pieces = board.occupancy(us);
     let their_pieces = board.occupancy(us.opposite());
 
-    let attacks = KING_ATTACKS[king_sq] & !our_pieces & !info.opponent_attacks;
+    let attacks = KING_ATTACKS[king_sq] & targets & !info.opponent_attacks;
 
     for to in BitIter(attacks) {
         let flag = if their_pieces & (1 << to) != 0 {
@@ -601,6 +800,9 @@ fn generate_king_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList) {
     }
 }
 
+// The king and the castling rook may start anywhere on the back rank in
+// Chess960. Every square either of them crosses, apart from their own, must
+// be empty and the king must not pass through or land on an attacked square.
 fn generate_castling_moves(board: &Board, info: &AttackInfo, moves: &mut MoveList) {
     // No castling when in check
     if info.in_check() {
@@ -608,45 +810,39 @@ fn generate_castling_moves(board: &Board, info: &AttackInfo, moves: &mut MoveLis
     }
 
     let us = board.turn;
-    let occ = board.all_occupancy();
+    let king_sq = board.king_square(us);
+    let rights = match us {
+        Color::White => [(WK_CASTLE, FLAG_KING_CASTLE), (WQ_CASTLE, FLAG_QUEEN_CASTLE)],
+        Color::Black => [(BK_CASTLE, FLAG_KING_CASTLE), (BQ_CASTLE, FLAG_QUEEN_CASTLE)],
+    };
+    let them = us.opposite();
+    let their_sliders = board.pieces(PieceType::Rook, them) | board.pieces(PieceType::Queen, them);
 
-    match us {
-        Color::White => {
-            // Kingside (e1-g1)
-            if board.castling.has(WK_CASTLE) {
-                let between_mask = 0x60u64; // f1, g1
-                let attack_mask = 0x60u64; // f1, g1 must not be attacked
-                if occ & between_mask == 0 && info.opponent_attacks & attack_mask == 0 {
-                    moves.push(Move::new(4, 6, FLAG_KING_CASTLE));
-                }
-            }
-            // Queenside (e1-c1)
-            if board.castling.has(WQ_CASTLE) {
-                let between_mask = 0x0Eu64; // b1, c1, d1
-                let attack_mask = 0x0Cu64; // c1, d1 must not be attacked
-                if occ & between_mask == 0 && info.opponent_attacks & attack_mask == 0 {
-                    moves.push(Move::new(4, 2, FLAG_QUEEN_CASTLE));
-                }
-            }
+    for (right, flag) in rights {
+        if !board.castling.has(right) {
+            continue;
         }
-        Color::Black => {
-            // Kingside (e8-g8)
-            if board.castling.has(BK_CASTLE) {
-                let between_mask = 0x6000000000000000u64; // f8, g8
-                let attack_mask = 0x6000000000000000u64;
-                if occ & between_mask == 0 && info.opponent_attacks & attack_mask == 0 {
-                    moves.push(Move::new(60, 62, FLAG_KING_CASTLE));
-                }
-            }
-            // Queenside (e8-c8)
-            if board.castling.has(BQ_CASTLE) {
-                let between_mask = 0x0E00000000000000u64; // b8, c8, d8
-                let attack_mask = 0x0C00000000000000u64; // c8, d8
-                if occ & between_mask == 0 && info.opponent_attacks & attack_mask == 0 {
-                    moves.push(Move::new(60, 58, FLAG_QUEEN_CASTLE));
-                }
-            }
+
+        let rook_sq = board.castling_rooks[right.trailing_zeros() as usize] as usize;
+        let mv = Move::new(king_sq, rook_sq, flag);
+        let (king_to, rook_to) = mv.castling_squares();
+        let king_path = BETWEEN[king_sq][king_to] | (1u64 << king_to);
+        let rook_path = BETWEEN[rook_sq][rook_to] | (1u64 << rook_to);
+        let occ = board.all_occupancy() & !(1u64 << king_sq) & !(1u64 << rook_sq);
+
+        if (king_path | rook_path) & occ != 0 {
+            continue;
+        }
+        if king_path & !(1u64 << king_sq) & info.opponent_attacks != 0 {
+            continue;
         }
+        // The castling rook may have been blocking a rook or queen on the
+        // back rank from the king's destination
+        if rook_attacks(king_to, occ | (1u64 << rook_to)) & their_sliders != 0 {
+            continue;
+        }
+
+        moves.push(mv);
     }
 }
 
@@ -695,6 +891,45 @@ mod tests {
         assert_eq!(mv.to_uci(), "e7e8q");
     }
 
+    #[test]
+    fn test_from_uci_infers_flags() {
+        let board = Board::default();
+        assert_eq!(
+            Move::from_uci(&board, "e2e4").unwrap(),
+            Move::new(12, 28, FLAG_DOUBLE_PUSH)
+        );
+        assert_eq!(Move::from_uci(&board, "g1f3").unwrap(), Move::new(6, 21, FLAG_QUIET));
+
+        let board = Board::from_fen("r3k3/1P6/8/3pP3/8/8/8/R3K2R w KQq d6 0 1").unwrap();
+        assert_eq!(Move::from_uci(&board, "e1g1").unwrap().flags(), FLAG_KING_CASTLE);
+        assert_eq!(Move::from_uci(&board, "e1c1").unwrap().flags(), FLAG_QUEEN_CASTLE);
+        assert_eq!(Move::from_uci(&board, "e5d6").unwrap().flags(), FLAG_EP_CAPTURE);
+        assert_eq!(Move::from_uci(&board, "b7a8n").unwrap().flags(), FLAG_PROMO_CAPTURE_N);
+        assert_eq!(Move::from_uci(&board, "b7b8q").unwrap().flags(), FLAG_PROMO_Q);
+    }
+
+    #[test]
+    fn test_from_uci_errors() {
+        let reason = |board: &Board, s: &str| match Move::from_uci(board, s) {
+            Err(Error::InvalidMove(_, reason)) => reason,
+            other => panic!("{} gave {:?}", s, other),
+        };
+        let board = Board::default();
+
+        for s in ["", "e2", "e2e4qq", "e9e4", "i2i4", "e2e4k", "e2-e4"] {
+            assert_eq!(reason(&board, s), MoveError::Syntax, "{}", s);
+        }
+        assert_eq!(reason(&board, "e3e4"), MoveError::NoPiece);
+        assert_eq!(reason(&board, "e7e5"), MoveError::NoPiece);
+        assert_eq!(reason(&board, "e2e5"), MoveError::Illegal);
+        assert_eq!(reason(&board, "e1g1"), MoveError::Illegal);
+
+        // Promotions need the piece letter
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(reason(&board, "b7b8"), MoveError::Illegal);
+        assert_eq!(reason(&board, "e1e2q"), MoveError::Illegal);
+    }
+
     #[test]
     fn test_check_detection() {
         // Position with white king in check from black queen
@@ -725,6 +960,34 @@ mod tests {
         assert_eq!(castle_moves.len(), 2); // Both kingside and queenside
     }
 
+    #[test]
+    fn test_chess960_castling() {
+        // The king stays on g1 when castling kingside
+        let board = Board::from_fen("1r4kr/8/8/8/8/8/8/1R4KR w HBhb - 0 1").unwrap();
+        let kingside = Move::from_uci(&board, "g1h1").unwrap();
+        assert_eq!(kingside, Move::new(6, 7, FLAG_KING_CASTLE));
+        assert_eq!(Move::from_uci(&board, "g1g1").unwrap(), kingside);
+        assert_eq!(kingside.to_uci(), "g1g1");
+        assert_eq!(kingside.to_uci_with(true), "g1h1");
+
+        let after = board.make_move(kingside);
+        assert_eq!(after.to_fen(), "1r4kr/8/8/8/8/8/8/1R3RK1 b kq - 1 1");
+
+        let queenside = Move::from_uci(&board, "g1b1").unwrap();
+        assert_eq!(Move::from_uci(&board, "g1c1").unwrap(), queenside);
+        let mut undone = board;
+        let undo = undone.make_move_mut(queenside);
+        assert_eq!(undone.to_fen(), "1r4kr/8/8/8/8/8/8/2KR3R b kq - 1 1");
+        undone.unmake_move(queenside, undo);
+        assert_eq!(undone, board);
+
+        // The king can't land on c1 once the b1 rook stops shielding it
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/rR4K1 w B - 0 1").unwrap();
+        assert!(!generate_moves(&board).iter().any(|mv| mv.is_castle()));
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/1R4K1 w B - 0 1").unwrap();
+        assert!(generate_moves(&board).iter().any(|mv| mv.is_castle()));
+    }
+
     #[test]
     fn test_en_passant() {
         // Position where white can en passant
@@ -738,4 +1001,47 @@ mod tests {
             .collect();
         assert_eq!(ep_moves.len(), 1);
     }
+
+    #[test]
+    fn test_en_passant_in_check() {
+        let ep_moves = |fen: &str| -> Vec<String> {
+            let board = Board::from_fen(fen).unwrap();
+            generate_moves(&board)
+                .iter()
+                .filter(|m| m.flags() == FLAG_EP_CAPTURE)
+                .map(|m| m.to_uci())
+                .collect()
+        };
+
+        // Taking the pawn that just gave check
+        assert_eq!(ep_moves("4k3/8/8/3pP3/4K3/8/8/8 w - d6 0 1"), ["e5d6"]);
+        // The double push uncovered a check that the capture doesn't answer
+        assert!(ep_moves("8/8/8/8/3Pp3/4k3/8/2B1K3 b - d3 0 1").is_empty());
+        // Both pawns leave the rank between king and rook
+        assert!(ep_moves("8/8/8/KPp4r/8/8/8/4k3 w - c6 0 1").is_empty());
+    }
+
+    #[test]
+    fn test_generate_captures_matches_full_generation() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "rnb1kbnr/pppp1ppp/8/4p3/7q/5P2/PPPPP1PP/RNBQKBNR w KQkq - 0 1",
+        ];
+
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let mut expected: Vec<Move> = generate_moves(&board)
+                .iter()
+                .copied()
+                .filter(|mv| mv.is_capture() || mv.flags() == FLAG_PROMO_Q)
+                .collect();
+            let mut captures: Vec<Move> = generate_captures(&board).iter().copied().collect();
+
+            expected.sort_by_key(|mv| mv.to_bits());
+            captures.sort_by_key(|mv| mv.to_bits());
+            assert_eq!(captures, expected, "{}", fen);
+        }
+    }
 }
@@ -0,0 +1,415 @@
+use crate::board::{Board, Color, PieceType};
+use crate::eval::piece_value;
+use crate::movegen::{generate, is_legal, AttackInfo, GenType, Move, MoveList};
+use crate::search::MAX_PLY;
+use crate::see::see_ge;
+
+// History scores stay within this bound, which also limits how far a single
+// bonus can move them
+const HISTORY_MAX: i32 = 16_384;
+
+// What the search has learned about quiet moves, used to order them
+pub struct HistoryTables {
+    // Two quiet moves per ply that recently caused a beta cutoff
+    killers: [[Move; 2]; MAX_PLY],
+    // The quiet reply that refuted a move, indexed by its from and to squares
+    counters: [[Move; 64]; 64],
+    // Butterfly history, indexed by side to move, from and to squares
+    history: [[[i32; 64]; 64]; 2],
+}
+
+impl HistoryTables {
+    pub fn new() -> Self {
+        Self {
+            killers: [[Move::default(); 2]; MAX_PLY],
+            counters: [[Move::default(); 64]; 64],
+            history: [[[0; 64]; 64]; 2],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    // Killers belong to the previous search's tree, the history is only
+    // faded so that what it learned carries over to the next move
+    pub fn new_search(&mut self) {
+        self.killers = [[Move::default(); 2]; MAX_PLY];
+        for score in self.history.iter_mut().flatten().flatten() {
+            *score /= 2;
+        }
+    }
+
+    pub fn killers(&self, ply: usize) -> [Move; 2] {
+        self.killers[ply]
+    }
+
+    // The reply that last refuted `prev`, if there is one
+    pub fn counter(&self, prev: Option<Move>) -> Option<Move> {
+        let prev = prev?;
+        let counter = self.counters[prev.from()][prev.to()];
+        (counter != Move::default()).then_some(counter)
+    }
+
+    pub fn history(&self, color: Color, mv: Move) -> i32 {
+        self.history[color.index()][mv.from()][mv.to()]
+    }
+
+    // `best` caused a beta cutoff after the quiet moves in `tried` failed to
+    pub fn update_quiets(
+        &mut self,
+        color: Color,
+        ply: usize,
+        prev: Option<Move>,
+        best: Move,
+        tried: &[Move],
+        depth: u32,
+    ) {
+        let killers = &mut self.killers[ply];
+        if killers[0] != best {
+            killers[1] = killers[0];
+            killers[0] = best;
+        }
+        if let Some(prev) = prev {
+            self.counters[prev.from()][prev.to()] = best;
+        }
+
+        let bonus = (depth * depth).min(HISTORY_MAX as u32) as i32;
+        self.add_history(color, best, bonus);
+        for &mv in tried.iter().filter(|&&mv| mv != best) {
+            self.add_history(color, mv, -bonus);
+        }
+    }
+
+    // Scores approach the bound instead of growing without limit
+    fn add_history(&mut self, color: Color, mv: Move, bonus: i32) {
+        let score = &mut self.history[color.index()][mv.from()][mv.to()];
+        *score += bonus - *score * bonus.abs() / HISTORY_MAX;
+    }
+}
+
+impl Default for HistoryTables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    TtMove,
+    GenerateCaptures,
+    Captures,
+    FirstKiller,
+    SecondKiller,
+    Counter,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// Hands out the legal moves of a position best first, generating each group
+// only when the previous one is used up: the hash move, captures that don't
+// lose material by MVV-LVA, the killers, the counter-move, the remaining
+// quiet moves by history and last the losing captures. A cutoff on the hash
+// move means nothing is generated at all.
+pub struct MovePicker {
+    stage: Stage,
+    info: AttackInfo,
+    tt_move: Option<Move>,
+    killers: [Move; 2],
+    counter: Option<Move>,
+    // Only captures that don't lose material are handed out, quiet moves and
+    // losing captures are left out
+    skip_bad_captures: bool,
+    moves: MoveList,
+    scores: [i32; 256],
+    next: usize,
+    // Captures that lose material by SEE, in the order they were picked
+    bad_captures: MoveList,
+    next_bad: usize,
+}
+
+impl MovePicker {
+    pub fn new(
+        board: &Board,
+        tt_move: Option<Move>,
+        killers: [Move; 2],
+        counter: Option<Move>,
+    ) -> Self {
+        Self {
+            stage: Stage::TtMove,
+            info: AttackInfo::new(board),
+            tt_move,
+            killers,
+            counter,
+            skip_bad_captures: false,
+            moves: MoveList::new(),
+            scores: [0; 256],
+            next: 0,
+            bad_captures: MoveList::new(),
+            next_bad: 0,
+        }
+    }
+
+    // Captures and queen promotions for the quiescence search, every evasion
+    // when in check. Captures that lose material are left out, standing pat
+    // already scores better than them.
+    pub fn quiescence(board: &Board) -> Self {
+        let mut picker = Self::new(board, None, [Move::default(); 2], None);
+        picker.skip_bad_captures = !picker.info.in_check();
+        picker
+    }
+
+    pub fn in_check(&self) -> bool {
+        self.info.in_check()
+    }
+
+    pub fn next(&mut self, board: &Board, tables: &HistoryTables) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if let Some(mv) = self.tt_move.filter(|&mv| is_legal(board, &self.info, mv)) {
+                        return Some(mv);
+                    }
+                    self.tt_move = None;
+                }
+                Stage::GenerateCaptures => {
+                    self.load(board, GenType::Captures, mvv_lva);
+                    self.stage = Stage::Captures;
+                }
+                Stage::Captures => match self.pick_best() {
+                    Some(mv) if Some(mv) == self.tt_move => {}
+                    Some(mv) if !see_ge(board, mv, 0) => {
+                        if !self.skip_bad_captures {
+                            self.bad_captures.push(mv);
+                        }
+                    }
+                    Some(mv) => return Some(mv),
+                    None if self.skip_bad_captures => self.stage = Stage::Done,
+                    None => self.stage = Stage::FirstKiller,
+                },
+                Stage::FirstKiller | Stage::SecondKiller | Stage::Counter => {
+                    let mv = match self.stage {
+                        Stage::FirstKiller => Some(self.killers[0]),
+                        Stage::SecondKiller => Some(self.killers[1]),
+                        _ => self
+                            .counter
+                            .filter(|counter| !self.killers.contains(counter)),
+                    };
+                    self.stage = match self.stage {
+                        Stage::FirstKiller => Stage::SecondKiller,
+                        Stage::SecondKiller => Stage::Counter,
+                        _ => Stage::GenerateQuiets,
+                    };
+                    if let Some(mv) = mv.filter(|&mv| self.is_refutation(board, mv)) {
+                        return Some(mv);
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    let color = board.side_to_move();
+                    self.load(board, GenType::Quiets, |_, mv| tables.history(color, mv));
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.pick_best() {
+                    Some(mv) if Some(mv) == self.tt_move || self.is_special_quiet(mv) => {}
+                    Some(mv) => return Some(mv),
+                    None => self.stage = Stage::BadCaptures,
+                },
+                Stage::BadCaptures => {
+                    let Some(&mv) = self.bad_captures.as_slice().get(self.next_bad) else {
+                        self.stage = Stage::Done;
+                        continue;
+                    };
+                    self.next_bad += 1;
+                    return Some(mv);
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    fn load(&mut self, board: &Board, gen: GenType, score: impl Fn(&Board, Move) -> i32) {
+        self.moves = generate(board, &self.info, gen);
+        self.next = 0;
+        for (i, &mv) in self.moves.iter().enumerate() {
+            self.scores[i] = score(board, mv);
+        }
+    }
+
+    // Selection sort one move at a time, a cutoff usually comes early
+    fn pick_best(&mut self) -> Option<Move> {
+        let len = self.moves.len();
+        if self.next >= len {
+            return None;
+        }
+
+        let best = (self.next..len).max_by_key(|&i| self.scores[i])?;
+        self.moves.as_mut_slice().swap(self.next, best);
+        self.scores.swap(self.next, best);
+        self.next += 1;
+
+        Some(self.moves.as_slice()[self.next - 1])
+    }
+
+    // Killers and the counter-move come from other positions, they are only
+    // tried if they are legal quiet moves here that haven't been tried yet
+    fn is_refutation(&self, board: &Board, mv: Move) -> bool {
+        mv != Move::default()
+            && Some(mv) != self.tt_move
+            && !mv.is_capture()
+            && mv.promotion_piece() != Some(PieceType::Queen)
+            && is_legal(board, &self.info, mv)
+    }
+
+    // Quiet moves already handed out in the killer and counter stages
+    fn is_special_quiet(&self, mv: Move) -> bool {
+        self.killers.contains(&mv) || self.counter == Some(mv)
+    }
+}
+
+// Most valuable victim, least valuable attacker
+pub fn mvv_lva(board: &Board, mv: Move) -> i32 {
+    let attacker = board
+        .piece_at(mv.from())
+        .map_or(0, |piece| piece_value(piece.0));
+    let victim = if mv.is_capture() {
+        // En passant is the only capture onto an empty square
+        board
+            .piece_at(mv.to())
+            .map_or(piece_value(PieceType::Pawn), |piece| piece_value(piece.0))
+    } else {
+        0
+    };
+    let promotion = mv.promotion_piece().map_or(0, piece_value);
+
+    (victim + promotion) * 16 - attacker / 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::generate_moves;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn picked(picker: &mut MovePicker, board: &Board, tables: &HistoryTables) -> Vec<Move> {
+        std::iter::from_fn(|| picker.next(board, tables)).collect()
+    }
+
+    fn sorted(mut moves: Vec<Move>) -> Vec<Move> {
+        moves.sort_by_key(|mv| mv.to_bits());
+        moves
+    }
+
+    #[test]
+    fn test_yields_same_moves_as_generate_moves() {
+        let mut tables = HistoryTables::new();
+        let fens = [
+            KIWIPETE,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            // In check, and in double check
+            "4k3/8/8/8/8/8/4q3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/5n2/8/r3K3 w - - 0 1",
+            "1r4kr/8/8/8/8/8/8/1R4KR w HBhb - 0 1",
+        ];
+
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let legal: Vec<Move> = generate_moves(&board).iter().copied().collect();
+
+            // Hash move, killers and counter-move both legal and not, quiet
+            // and capture, must never add or repeat a move
+            let noise = [legal[0], legal[legal.len() / 2], Move::new(0, 63, 0)];
+            tables.killers[3] = [legal[legal.len() - 1], noise[2]];
+            for tt_move in [None, Some(noise[0]), Some(noise[1]), Some(noise[2])] {
+                let mut picker =
+                    MovePicker::new(&board, tt_move, tables.killers(3), Some(noise[1]));
+                let moves = picked(&mut picker, &board, &tables);
+                assert_eq!(moves.len(), legal.len(), "{} {:?}", fen, tt_move);
+                assert_eq!(sorted(moves), sorted(legal.clone()), "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stage_order() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let uci = |s: &str| Move::from_uci(&board, s).unwrap();
+        let mut tables = HistoryTables::new();
+        tables.add_history(Color::White, uci("a2a3"), 500);
+        tables.add_history(Color::White, uci("b2b3"), 100);
+
+        let mut picker = MovePicker::new(
+            &board,
+            Some(uci("e1g1")),
+            [uci("d5d6"), uci("g2g3")],
+            Some(uci("h1f1")),
+        );
+        let moves = picked(&mut picker, &board, &tables);
+
+        assert_eq!(moves[0], uci("e1g1"));
+        // The bishop on a6 is the most valuable victim
+        assert_eq!(&moves[1..4], &[uci("e2a6"), uci("d5e6"), uci("g2h3")]);
+        assert_eq!(&moves[4..7], &[uci("d5d6"), uci("g2g3"), uci("h1f1")]);
+        assert_eq!(&moves[7..9], &[uci("a2a3"), uci("b2b3")]);
+        // Knights taking defended pawns, the queen taking the f6 knight and
+        // the h3 pawn, which the h8 rook defends down the open file
+        let bad = &moves[moves.len() - 5..];
+        assert_eq!(bad[0], uci("f3f6"));
+        assert_eq!(bad[4], uci("f3h3"));
+        assert!(bad
+            .iter()
+            .all(|&mv| mv.is_capture() && !see_ge(&board, mv, 0)));
+    }
+
+    #[test]
+    fn test_quiescence_picker() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let tables = HistoryTables::new();
+        let moves = picked(&mut MovePicker::quiescence(&board), &board, &tables);
+        // Five of the eight captures lose material
+        assert_eq!(moves.len(), 3);
+        assert!(moves
+            .iter()
+            .all(|&mv| mv.is_capture() && see_ge(&board, mv, 0)));
+
+        // Every evasion when in check
+        let board = Board::from_fen("4k3/8/8/8/8/8/4q3/4K3 w - - 0 1").unwrap();
+        let moves = picked(&mut MovePicker::quiescence(&board), &board, &tables);
+        assert_eq!(moves.len(), generate_moves(&board).len());
+    }
+
+    #[test]
+    fn test_update_quiets() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let uci = |s: &str| Move::from_uci(&board, s).unwrap();
+        let mut tables = HistoryTables::new();
+
+        tables.update_quiets(
+            Color::White,
+            2,
+            Some(uci("e2a6")),
+            uci("a2a3"),
+            &[uci("b2b3")],
+            4,
+        );
+        tables.update_quiets(Color::White, 2, None, uci("g2g3"), &[], 4);
+        assert_eq!(tables.killers(2), [uci("g2g3"), uci("a2a3")]);
+        assert_eq!(tables.counter(Some(uci("e2a6"))), Some(uci("a2a3")));
+        assert_eq!(tables.counter(Some(uci("a2a3"))), None);
+        assert!(tables.history(Color::White, uci("a2a3")) > 0);
+        assert!(tables.history(Color::White, uci("b2b3")) < 0);
+        assert_eq!(tables.history(Color::Black, uci("a2a3")), 0);
+
+        for _ in 0..1000 {
+            tables.add_history(Color::White, uci("a2a3"), 4096);
+        }
+        assert!(tables.history(Color::White, uci("a2a3")) <= HISTORY_MAX);
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::timeman::DEFAULT_MOVE_OVERHEAD;
+use crate::tt::DEFAULT_HASH_MB;
+
+pub const MAX_HASH_MB: i64 = 65536;
+pub const MAX_THREADS: i64 = 256;
+pub const MAX_MULTI_PV: i64 = 256;
+pub const MAX_MOVE_OVERHEAD_MS: i64 = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionKind {
+    Spin {
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    Check {
+        default: bool,
+    },
+    Combo {
+        default: &'static str,
+        vars: &'static [&'static str],
+    },
+    Button,
+    String {
+        default: &'static str,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionValue {
+    Spin(i64),
+    Check(bool),
+    Combo(String),
+    Button,
+    String(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UciOption {
+    pub name: &'static str,
+    pub kind: OptionKind,
+}
+
+// Every option advertised in response to `uci`, in the order they are listed
+pub const OPTIONS: &[UciOption] = &[
+    UciOption {
+        name: "Hash",
+        kind: OptionKind::Spin {
+            default: DEFAULT_HASH_MB as i64,
+            min: 1,
+            max: MAX_HASH_MB,
+        },
+    },
+    UciOption {
+        name: "Threads",
+        kind: OptionKind::Spin {
+            default: 1,
+            min: 1,
+            max: MAX_THREADS,
+        },
+    },
+    UciOption {
+        name: "Clear Hash",
+        kind: OptionKind::Button,
+    },
+    UciOption {
+        name: "MultiPV",
+        kind: OptionKind::Spin {
+            default: 1,
+            min: 1,
+            max: MAX_MULTI_PV,
+        },
+    },
+    UciOption {
+        name: "Move Overhead",
+        kind: OptionKind::Spin {
+            default: DEFAULT_MOVE_OVERHEAD.as_millis() as i64,
+            min: 0,
+            max: MAX_MOVE_OVERHEAD_MS,
+        },
+    },
+    UciOption {
+        name: "Ponder",
+        kind: OptionKind::Check { default: false },
+    },
+    UciOption {
+        name: "UCI_Chess960",
+        kind: OptionKind::Check { default: false },
+    },
+];
+
+// Option names are case insensitive
+pub fn find_option(name: &str) -> Option<&'static UciOption> {
+    OPTIONS
+        .iter()
+        .find(|option| option.name.eq_ignore_ascii_case(name))
+}
+
+impl UciOption {
+    pub fn default_value(&self) -> OptionValue {
+        match self.kind {
+            OptionKind::Spin { default, .. } => OptionValue::Spin(default),
+            OptionKind::Check { default } => OptionValue::Check(default),
+            OptionKind::Combo { default, .. } => OptionValue::Combo(default.to_string()),
+            OptionKind::Button => OptionValue::Button,
+            OptionKind::String { default } => OptionValue::String(default.to_string()),
+        }
+    }
+
+    // Validates the value of a `setoption` command against the option's type
+    pub fn parse(&self, value: Option<&str>) -> Result<OptionValue> {
+        let invalid = |reason: String| Error::InvalidOption(format!("{}: {}", self.name, reason));
+        let required = || value.ok_or_else(|| invalid("missing value".to_string()));
+
+        match self.kind {
+            OptionKind::Spin { min, max, .. } => {
+                let value = required()?;
+                let n: i64 = value
+                    .parse()
+                    .map_err(|_| invalid(format!("'{}' is not a number", value)))?;
+                if n < min || n > max {
+                    return Err(invalid(format!("{} is outside {}..{}", n, min, max)));
+                }
+                Ok(OptionValue::Spin(n))
+            }
+            OptionKind::Check { .. } => match required()? {
+                "true" => Ok(OptionValue::Check(true)),
+                "false" => Ok(OptionValue::Check(false)),
+                other => Err(invalid(format!("'{}' is not true or false", other))),
+            },
+            OptionKind::Combo { vars, .. } => {
+                let value = required()?;
+                vars.iter()
+                    .find(|var| var.eq_ignore_ascii_case(value))
+                    .map(|var| OptionValue::Combo(var.to_string()))
+                    .ok_or_else(|| {
+                        invalid(format!("'{}' is not one of {}", value, vars.join(", ")))
+                    })
+            }
+            OptionKind::Button => Ok(OptionValue::Button),
+            // <empty> is how GUIs send an empty string
+            OptionKind::String { .. } => match value {
+                None | Some("<empty>") => Ok(OptionValue::String(String::new())),
+                Some(value) => Ok(OptionValue::String(value.to_string())),
+            },
+        }
+    }
+}
+
+impl fmt::Display for UciOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match self.kind {
+            OptionKind::Spin { default, min, max } => {
+                write!(f, "spin default {} min {} max {}", default, min, max)
+            }
+            OptionKind::Check { default } => write!(f, "check default {}", default),
+            OptionKind::Combo { default, vars } => {
+                write!(f, "combo default {}", default)?;
+                for var in vars {
+                    write!(f, " var {}", var)?;
+                }
+                Ok(())
+            }
+            OptionKind::Button => write!(f, "button"),
+            OptionKind::String { default: "" } => write!(f, "string default <empty>"),
+            OptionKind::String { default } => write!(f, "string default {}", default),
+        }
+    }
+}
+
+// Current values of all registered options
+#[derive(Debug, Clone)]
+pub struct Options {
+    values: Vec<OptionValue>,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self {
+            values: OPTIONS.iter().map(UciOption::default_value).collect(),
+        }
+    }
+
+    // Validates and stores a new value, the caller applies any side effects
+    pub fn set(
+        &mut self,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<(&'static UciOption, OptionValue)> {
+        let idx = OPTIONS
+            .iter()
+            .position(|option| option.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::InvalidOption(format!("unknown option '{}'", name)))?;
+        let option = &OPTIONS[idx];
+        let value = option.parse(value)?;
+        self.values[idx] = value.clone();
+
+        Ok((option, value))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OptionValue> {
+        OPTIONS
+            .iter()
+            .position(|option| option.name.eq_ignore_ascii_case(name))
+            .map(|idx| &self.values[idx])
+    }
+
+    pub fn spin(&self, name: &str) -> i64 {
+        match self.get(name) {
+            Some(OptionValue::Spin(value)) => *value,
+            _ => panic!("no spin option named {}", name),
+        }
+    }
+
+    pub fn check(&self, name: &str) -> bool {
+        match self.get(name) {
+            Some(OptionValue::Check(value)) => *value,
+            _ => panic!("no check option named {}", name),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advertised_options() {
+        let lines: Vec<String> = OPTIONS.iter().map(|option| option.to_string()).collect();
+
+        assert!(
+            lines.contains(&"option name Hash type spin default 16 min 1 max 65536".to_string())
+        );
+        assert!(lines.contains(&"option name Clear Hash type button".to_string()));
+        assert!(lines.contains(&"option name UCI_Chess960 type check default false".to_string()));
+        for name in ["Threads", "MultiPV", "Move Overhead", "Ponder"] {
+            assert!(find_option(name).is_some(), "{} is not registered", name);
+        }
+    }
+
+    #[test]
+    fn test_set_validates_values() {
+        let mut options = Options::new();
+
+        options.set("hash", Some("64")).unwrap();
+        assert_eq!(options.spin("Hash"), 64);
+        assert!(options.set("Hash", Some("0")).is_err());
+        assert!(options.set("Hash", Some("lots")).is_err());
+        assert!(options.set("Hash", None).is_err());
+        assert_eq!(options.spin("Hash"), 64);
+
+        options.set("Ponder", Some("true")).unwrap();
+        assert!(options.check("Ponder"));
+        assert!(options.set("Ponder", Some("yes")).is_err());
+
+        assert!(options.set("Clear Hash", None).is_ok());
+        assert!(options.set("Contempt", Some("10")).is_err());
+    }
+
+    #[test]
+    fn test_combo_and_string() {
+        let combo = UciOption {
+            name: "Style",
+            kind: OptionKind::Combo {
+                default: "Normal",
+                vars: &["Solid", "Normal", "Risky"],
+            },
+        };
+        assert_eq!(
+            combo.to_string(),
+            "option name Style type combo default Normal var Solid var Normal var Risky"
+        );
+        assert_eq!(
+            combo.parse(Some("risky")).unwrap(),
+            OptionValue::Combo("Risky".to_string())
+        );
+        assert!(combo.parse(Some("Reckless")).is_err());
+
+        let string = UciOption {
+            name: "Book File",
+            kind: OptionKind::String { default: "" },
+        };
+        assert_eq!(
+            string.to_string(),
+            "option name Book File type string default <empty>"
+        );
+        assert_eq!(
+            string.parse(Some("<empty>")).unwrap(),
+            OptionValue::String(String::new())
+        );
+        assert_eq!(
+            string.parse(Some("book.bin")).unwrap(),
+            OptionValue::String("book.bin".to_string())
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use crate::board::Board;
+use crate::movegen::{generate_moves, Move};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerftOptions {
+    // Size of the table caching subtree counts, 0 disables it
+    pub hash_mb: usize,
+    // Root moves are shared out between this many threads
+    pub threads: usize,
+    // Print castling as king takes rook in the divide
+    pub chess960: bool,
+}
+
+impl Default for PerftOptions {
+    fn default() -> Self {
+        Self {
+            hash_mb: 0,
+            threads: 1,
+            chess960: false,
+        }
+    }
+}
+
+// Leaf nodes `depth` plies below the position
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    perft_with(board, depth, PerftOptions::default())
+}
+
+// Leaf nodes below each legal move, in move generation order
+pub fn perft_divide(board: &Board, depth: u32) -> Vec<(Move, u64)> {
+    perft_divide_with(board, depth, PerftOptions::default())
+}
+
+pub fn perft_with(board: &Board, depth: u32, options: PerftOptions) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    perft_divide_with(board, depth, options)
+        .iter()
+        .map(|(_, nodes)| nodes)
+        .sum()
+}
+
+pub fn perft_divide_with(board: &Board, depth: u32, options: PerftOptions) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+
+    let moves = generate_moves(board);
+    let table = (options.hash_mb > 0).then(|| PerftTable::new(options.hash_mb));
+    let table = table.as_ref();
+    let threads = options.threads.clamp(1, moves.len().max(1));
+
+    if threads == 1 {
+        let mut board = *board;
+        return moves
+            .iter()
+            .map(|&mv| {
+                let undo = board.make_move_mut(mv);
+                let nodes = count(&mut board, depth - 1, table);
+                board.unmake_move(mv, undo);
+                (mv, nodes)
+            })
+            .collect();
+    }
+
+    // Threads take the next unclaimed root move until none are left
+    let next = AtomicUsize::new(0);
+    let mut counts = vec![0; moves.len()];
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut board = *board;
+                    let mut done = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(&mv) = moves.as_slice().get(idx) else {
+                            break;
+                        };
+                        let undo = board.make_move_mut(mv);
+                        done.push((idx, count(&mut board, depth - 1, table)));
+                        board.unmake_move(mv, undo);
+                    }
+                    done
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            for (idx, nodes) in worker.join().expect("perft thread panicked") {
+                counts[idx] = nodes;
+            }
+        }
+    });
+
+    moves.iter().copied().zip(counts).collect()
+}
+
+// Prints the divide sorted by move, then the total and the speed, in the
+// format other engines use so that outputs can be diffed
+pub fn print_divide(board: &Board, depth: u32, options: PerftOptions) -> u64 {
+    let start = Instant::now();
+    let mut divide = perft_divide_with(board, depth, options);
+    divide.sort_by_key(|(mv, _)| mv.to_uci_with(options.chess960));
+
+    for (mv, nodes) in &divide {
+        println!("{}: {}", mv.to_uci_with(options.chess960), nodes);
+    }
+
+    let total = match depth {
+        0 => 1,
+        _ => divide.iter().map(|(_, nodes)| nodes).sum(),
+    };
+    let elapsed = start.elapsed();
+    let nps = (total as f64 / elapsed.as_secs_f64().max(1e-9)) as u64;
+
+    println!();
+    println!("Nodes searched: {}", total);
+    println!("Time: {} ms, {} nps", elapsed.as_millis(), nps);
+
+    total
+}
+
+fn count(board: &mut Board, depth: u32, table: Option<&PerftTable>) -> u64 {
+    // Bulk counting, the leaves themselves are never made
+    match depth {
+        0 => return 1,
+        1 => return generate_moves(board).len() as u64,
+        _ => {}
+    }
+
+    if let Some(nodes) = table.and_then(|table| table.probe(board.hash, depth)) {
+        return nodes;
+    }
+
+    let mut nodes = 0;
+    for mv in generate_moves(board).iter() {
+        let undo = board.make_move_mut(*mv);
+        nodes += count(board, depth - 1, table);
+        board.unmake_move(*mv, undo);
+    }
+
+    if let Some(table) = table {
+        table.store(board.hash, depth, nodes);
+    }
+
+    nodes
+}
+
+// Always-replace cache of subtree counts keyed by hash and depth. Slots are
+// stored as (key ^ nodes, nodes) like the search TT so that a torn write
+// between threads reads as a miss.
+struct PerftTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+
+impl PerftTable {
+    fn new(mb: usize) -> Self {
+        let count = (mb * 1024 * 1024 / std::mem::size_of::<[AtomicU64; 2]>()).max(1);
+        let mut slots = Vec::with_capacity(count);
+        slots.resize_with(count, Default::default);
+
+        Self { slots }
+    }
+
+    #[inline(always)]
+    fn key(hash: u64, depth: u32) -> u64 {
+        hash ^ (depth as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn slot(&self, key: u64) -> &[AtomicU64; 2] {
+        &self.slots[(key % self.slots.len() as u64) as usize]
+    }
+
+    // Empty slots hold zero nodes, which is never a stored count worth reusing
+    fn probe(&self, hash: u64, depth: u32) -> Option<u64> {
+        let key = Self::key(hash, depth);
+        let [check, nodes] = self.slot(key);
+        let nodes = nodes.load(Ordering::Relaxed);
+
+        (nodes != 0 && check.load(Ordering::Relaxed) ^ nodes == key).then_some(nodes)
+    }
+
+    fn store(&self, hash: u64, depth: u32, nodes: u64) {
+        let key = Self::key(hash, depth);
+        let [check, slot_nodes] = self.slot(key);
+        check.store(key ^ nodes, Ordering::Relaxed);
+        slot_nodes.store(nodes, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn test_perft_counts() {
+        let board = Board::default();
+        assert_eq!(perft(&board, 0), 1);
+        assert_eq!(perft(&board, 1), 20);
+        assert_eq!(perft(&board, 3), 8_902);
+
+        let kiwipete = Board::from_fen(KIWIPETE).unwrap();
+        assert_eq!(perft(&kiwipete, 3), 97_862);
+    }
+
+    #[test]
+    fn test_perft_divide() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let divide = perft_divide(&board, 2);
+
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2_039);
+        let castle = divide.iter().find(|(mv, _)| mv.to_uci() == "e1g1").unwrap();
+        assert_eq!(castle.1, 43);
+        assert!(perft_divide(&board, 0).is_empty());
+    }
+
+    #[test]
+    fn test_hashed_and_threaded_perft_agree() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let expected = perft_divide(&board, 3);
+
+        for options in [
+            PerftOptions {
+                hash_mb: 1,
+                threads: 1,
+                ..PerftOptions::default()
+            },
+            PerftOptions {
+                hash_mb: 0,
+                threads: 4,
+                ..PerftOptions::default()
+            },
+            PerftOptions {
+                hash_mb: 1,
+                threads: 4,
+                ..PerftOptions::default()
+            },
+        ] {
+            assert_eq!(
+                perft_divide_with(&board, 3, options),
+                expected,
+                "{:?}",
+                options
+            );
+        }
+        assert_eq!(
+            perft_with(
+                &board,
+                4,
+                PerftOptions {
+                    hash_mb: 4,
+                    threads: 2,
+                    ..PerftOptions::default()
+                }
+            ),
+            4_085_603
+        );
+    }
+}
@@ -0,0 +1,532 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::board::{Board, Color, GameStatus};
+use crate::error::{Error, Result};
+use crate::movegen::Move;
+use crate::san::{parse_san, to_san};
+
+// Tags every exported game carries, in this order, with their unknown values
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+// Export format keeps movetext lines at most this long
+const LINE_WIDTH: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown,
+}
+
+impl GameResult {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None,
+        }
+    }
+
+    // The result implied by the status of the final position, `side` being
+    // the side to move there
+    pub fn from_status(status: GameStatus, side: Color) -> Self {
+        match status {
+            GameStatus::Ongoing => GameResult::Unknown,
+            GameStatus::Checkmate if side == Color::White => GameResult::BlackWins,
+            GameStatus::Checkmate => GameResult::WhiteWins,
+            _ => GameResult::Draw,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// A game's main line. positions[0] is the starting position and
+// positions[i + 1] the position after moves[i].
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub positions: Vec<Board>,
+    pub moves: Vec<Move>,
+    pub result: GameResult,
+}
+
+impl Game {
+    pub fn new() -> Self {
+        Self::from_position(Board::default())
+    }
+
+    // Games not starting from the initial position record it in a FEN tag
+    pub fn from_position(board: Board) -> Self {
+        let mut game = Self {
+            tags: Vec::new(),
+            positions: vec![board],
+            moves: Vec::new(),
+            result: GameResult::Unknown,
+        };
+
+        if board != Board::default() {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &board.to_fen());
+        }
+
+        game
+    }
+
+    pub fn start(&self) -> &Board {
+        &self.positions[0]
+    }
+
+    // The current position
+    pub fn board(&self) -> &Board {
+        self.positions
+            .last()
+            .expect("a game has a starting position")
+    }
+
+    pub fn push(&mut self, mv: Move) {
+        let board = self.board().make_move(mv);
+        self.moves.push(mv);
+        self.positions.push(board);
+    }
+
+    // Keys of the positions before the current one, as `Board::status` expects
+    pub fn hashes(&self) -> Vec<u64> {
+        self.positions[..self.positions.len() - 1]
+            .iter()
+            .map(|board| board.hash)
+            .collect()
+    }
+
+    pub fn status(&self) -> GameStatus {
+        self.board().status(&self.hashes())
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Writes the game in PGN export format
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, unknown) in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.as_str(),
+                _ => self.tag(name).unwrap_or(unknown),
+            };
+            write_tag(f, name, value)?;
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.iter().any(|(roster, _)| roster == name) {
+                write_tag(f, name, value)?;
+            }
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::with_capacity(self.moves.len() * 3 / 2 + 1);
+        for (i, &mv) in self.moves.iter().enumerate() {
+            let board = &self.positions[i];
+            if board.turn == Color::White {
+                tokens.push(format!("{}.", board.fullmove));
+            } else if i == 0 {
+                tokens.push(format!("{}...", board.fullmove));
+            }
+            tokens.push(to_san(board, mv));
+        }
+        tokens.push(self.result.to_string());
+
+        let mut line = String::with_capacity(LINE_WIDTH);
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+fn write_tag(f: &mut fmt::Formatter<'_>, name: &str, value: &str) -> fmt::Result {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    writeln!(f, "[{} \"{}\"]", name, value)
+}
+
+// The first game of a PGN text
+impl FromStr for Game {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_pgn(s)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| pgn_error("no game found".to_string()))
+    }
+}
+
+#[inline(always)]
+fn pgn_error(message: String) -> Error {
+    Error::InvalidPgn(message)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    San(String),
+    Result(GameResult),
+}
+
+// Parses every game in a PGN text. Only the main line is kept: comments,
+// NAGs, variations and escaped lines are skipped.
+pub fn parse_pgn(text: &str) -> Result<Vec<Game>> {
+    let mut games = Vec::new();
+    let mut tags = Vec::new();
+    let mut moves = Vec::new();
+
+    for token in tokenize(text)? {
+        match token {
+            // A tag after movetext starts the next game, even without a result
+            Token::Tag(name, value) => {
+                if !moves.is_empty() {
+                    games.push(build_game(games.len(), tags, moves, None)?);
+                    tags = Vec::new();
+                    moves = Vec::new();
+                }
+                tags.push((name, value));
+            }
+            Token::San(san) => moves.push(san),
+            Token::Result(result) => {
+                games.push(build_game(games.len(), tags, moves, Some(result))?);
+                tags = Vec::new();
+                moves = Vec::new();
+            }
+        }
+    }
+
+    if !tags.is_empty() || !moves.is_empty() {
+        games.push(build_game(games.len(), tags, moves, None)?);
+    }
+
+    Ok(games)
+}
+
+fn build_game(
+    index: usize,
+    tags: Vec<(String, String)>,
+    moves: Vec<String>,
+    result: Option<GameResult>,
+) -> Result<Game> {
+    let error = |message: String| pgn_error(format!("game {}: {}", index + 1, message));
+
+    let start = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Board::from_fen_lenient(fen).map_err(|e| error(e.to_string()))?,
+        None => Board::default(),
+    };
+
+    let mut game = Game {
+        tags,
+        positions: vec![start],
+        moves: Vec::with_capacity(moves.len()),
+        result: GameResult::Unknown,
+    };
+
+    for san in &moves {
+        let mv = parse_san(game.board(), san).map_err(|e| error(e.to_string()))?;
+        game.push(mv);
+    }
+
+    game.result = result
+        .or_else(|| game.tag("Result").and_then(GameResult::parse))
+        .unwrap_or(GameResult::Unknown);
+
+    Ok(game)
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    // Nesting depth of the variation being skipped
+    let mut depth = 0;
+    let mut i = 0;
+
+    let skip_line = |i: &mut usize| {
+        while *i < chars.len() && chars[*i] != '\n' {
+            *i += 1;
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '%' if i == 0 || chars[i - 1] == '\n' => skip_line(&mut i),
+            ';' => skip_line(&mut i),
+            '{' => match chars[i..].iter().position(|&c| c == '}') {
+                Some(len) => i += len + 1,
+                None => return Err(pgn_error("unterminated comment".to_string())),
+            },
+            '(' => {
+                depth += 1;
+                i += 1;
+            }
+            ')' => {
+                if depth == 0 {
+                    return Err(pgn_error("unmatched ')'".to_string()));
+                }
+                depth -= 1;
+                i += 1;
+            }
+            '[' if depth == 0 => {
+                let (name, value, len) = parse_tag(&chars[i..])?;
+                tokens.push(Token::Tag(name, value));
+                i += len;
+            }
+            '$' => {
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !"{}()[];$".contains(chars[i])
+                {
+                    i += 1;
+                }
+                if i == start {
+                    return Err(pgn_error(format!("unexpected '{}'", c)));
+                }
+                let word: String = chars[start..i].iter().collect();
+                if depth == 0 {
+                    tokens.extend(word_token(&word));
+                }
+            }
+        }
+    }
+
+    if depth > 0 {
+        return Err(pgn_error("unterminated variation".to_string()));
+    }
+
+    Ok(tokens)
+}
+
+// `[Name "value"]`, returns the tag and the number of characters it spans
+fn parse_tag(chars: &[char]) -> Result<(String, String, usize)> {
+    let invalid = || {
+        let line: String = chars.iter().take_while(|&&c| c != '\n').collect();
+        pgn_error(format!("invalid tag {}", line))
+    };
+
+    let mut i = 1;
+    let name_start = i;
+    while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+        i += 1;
+    }
+    let name: String = chars[name_start..i].iter().collect();
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    if name.is_empty() || chars.get(i) != Some(&'"') {
+        return Err(invalid());
+    }
+
+    i += 1;
+    let mut value = String::new();
+    loop {
+        match chars.get(i) {
+            Some('\\') if matches!(chars.get(i + 1), Some('"' | '\\')) => {
+                value.push(chars[i + 1]);
+                i += 2;
+            }
+            Some('"') => break,
+            Some(&c) => {
+                value.push(c);
+                i += 1;
+            }
+            None => return Err(invalid()),
+        }
+    }
+
+    i += 1;
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    if chars.get(i) != Some(&']') {
+        return Err(invalid());
+    }
+
+    Ok((name, value, i + 1))
+}
+
+// A movetext word is a result, a move number, a move or a move number
+// glued to its move as in `1.e4` or `12...Nf6`
+fn word_token(word: &str) -> Option<Token> {
+    if let Some(result) = GameResult::parse(word) {
+        return Some(Token::Result(result));
+    }
+
+    let digits = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let san = match word[digits..].strip_prefix('.') {
+        Some(rest) if digits > 0 => rest.trim_start_matches('.'),
+        _ => word,
+    };
+
+    // Skip empty remains of move numbers and stand-alone annotations like `!?`
+    if san.chars().all(|c| c == '!' || c == '?') {
+        None
+    } else {
+        Some(Token::San(san.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERA_GAME: &str = r#"[Event "Paris"]
+[Site "Paris FRA"]
+[Date "1858.??.??"]
+[Round "?"]
+[White "Paul Morphy"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+[ECO "C41"]
+
+1.e4 e5 2.Nf3 d6 3.d4 Bg4 {This is a weak move already.} 4.dxe5 Bxf3 5.Qxf3 dxe5
+6.Bc4 Nf6 7.Qb3 qe7 8.Nc3 c6 9.Bg5 $6 (9.Qxb7?! Qb4+) b5 10.Nxb5 cxb5
+11.Bxb5+ Nbd7 12.O-O-O Rd8 13.Rxd7 Rxd7 14.Rd1 Qe6 15.Bxd7+ Nxd7
+16.Qb8+ Nxb8 17.Rd8# 1-0
+"#;
+
+    #[test]
+    fn test_parse_game() {
+        let game: Game = OPERA_GAME.parse().unwrap();
+
+        assert_eq!(game.tag("White"), Some("Paul Morphy"));
+        assert_eq!(game.tag("ECO"), Some("C41"));
+        assert_eq!(game.moves.len(), 33);
+        assert_eq!(game.positions.len(), 34);
+        assert_eq!(game.result, GameResult::WhiteWins);
+        assert_eq!(game.status(), GameStatus::Checkmate);
+        assert_eq!(game.moves[22].to_uci(), "e1c1");
+    }
+
+    #[test]
+    fn test_write_game() {
+        let game: Game = OPERA_GAME.parse().unwrap();
+        let pgn = game.to_string();
+
+        assert!(pgn.starts_with("[Event \"Paris\"]\n[Site \"Paris FRA\"]\n"));
+        assert!(pgn.contains("[Result \"1-0\"]\n[ECO \"C41\"]\n\n1. e4 e5 2. Nf3 d6"));
+        assert!(pgn.contains("7. Qb3 Qe7"));
+        assert!(pgn.trim_end().ends_with("17. Rd8# 1-0"));
+        assert!(pgn.lines().all(|line| line.len() <= LINE_WIDTH));
+
+        let again: Game = pgn.parse().unwrap();
+        assert_eq!(again.moves, game.moves);
+        assert_eq!(again.tags, game.tags);
+        assert_eq!(again.to_string(), pgn);
+    }
+
+    #[test]
+    fn test_from_position() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 40";
+        let mut game = Game::from_position(Board::from_fen(fen).unwrap());
+        game.set_tag("White", "Someone \"Quoted\"");
+        game.push(Move::from_uci(game.board(), "e8d7").unwrap());
+        game.push(Move::from_uci(game.board(), "e2e4").unwrap());
+
+        let pgn = game.to_string();
+        assert!(pgn.contains("[White \"Someone \\\"Quoted\\\"\"]"));
+        assert!(pgn.contains("[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 40\"]"));
+        assert!(pgn.contains("\n40... Kd7 41. e4 *\n"));
+
+        let again: Game = pgn.parse().unwrap();
+        assert_eq!(again.tag("White"), Some("Someone \"Quoted\""));
+        assert_eq!(again.board(), game.board());
+        assert!(Game::new().tag("FEN").is_none());
+    }
+
+    #[test]
+    fn test_parse_several_games() {
+        let text = "% exported by hand\n\
+                    [Event \"One\"]\n\n1. e4 ; a rest of line comment\n e5 1/2-1/2\n\n\
+                    [Event \"Two\"]\n\n1.d4 d5 (1...Nf6 2.c4 (2.Nf3)) 2.c4 $1\n\n\
+                    [Event \"Three\"]\n[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4#";
+        let games = parse_pgn(text).unwrap();
+
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].result, GameResult::Draw);
+        assert_eq!(games[0].moves.len(), 2);
+        assert_eq!(games[1].tag("Event"), Some("Two"));
+        assert_eq!(games[1].moves.len(), 3);
+        assert_eq!(games[1].result, GameResult::Unknown);
+        assert_eq!(games[2].result, GameResult::BlackWins);
+        assert_eq!(
+            GameResult::from_status(games[2].status(), games[2].board().turn),
+            GameResult::BlackWins
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let message = |text: &str| match parse_pgn(text) {
+            Err(Error::InvalidPgn(message)) => message,
+            other => panic!("{:?} parsed as {:?}", text, other),
+        };
+
+        assert!(message("1. e4 e5 2. Ke3").starts_with("game 1: Invalid move: Ke3"));
+        assert!(message("[Event \"x\"] 1. e4 1-0 1. e5").starts_with("game 2:"));
+        assert!(message("[FEN \"8/8 w\"]").starts_with("game 1: Invalid FEN"));
+        assert_eq!(message("1. e4 {never closed"), "unterminated comment");
+        assert_eq!(message("1. e4 (1. d4"), "unterminated variation");
+        assert!(message("[Event Paris]").starts_with("invalid tag"));
+        assert!(parse_pgn("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+use crate::board::{Board, Color, Piece, PieceType, Square};
+use crate::error::{Error, MoveError, Result};
+use crate::movegen::{generate_moves, Move, FLAG_KING_CASTLE};
+
+// Standard Algebraic Notation for a legal move, with check and mate suffixes
+pub fn to_san(board: &Board, mv: Move) -> String {
+    let mut san = String::with_capacity(8);
+
+    if mv.is_castle() {
+        san.push_str(if mv.flags() == FLAG_KING_CASTLE {
+            "O-O"
+        } else {
+            "O-O-O"
+        });
+    } else {
+        let piece = board
+            .piece_at(mv.from())
+            .expect("SAN for a move from an empty square")
+            .0;
+        let from = Square::from_index(mv.from());
+
+        if piece == PieceType::Pawn {
+            if mv.is_capture() {
+                san.push((b'a' + from.file()) as char);
+            }
+        } else {
+            san.push(piece_letter(piece));
+
+            // Name the file if that tells the pieces apart, else the rank, else both
+            let others: Vec<Square> = generate_moves(board)
+                .iter()
+                .filter(|other| {
+                    other.to() == mv.to()
+                        && other.from() != mv.from()
+                        && board.piece_at(other.from()).map(|p| p.0) == Some(piece)
+                })
+                .map(|other| Square::from_index(other.from()))
+                .collect();
+
+            if !others.is_empty() {
+                let same_file = others.iter().any(|sq| sq.file() == from.file());
+                let same_rank = others.iter().any(|sq| sq.rank() == from.rank());
+                if !same_file || same_rank {
+                    san.push((b'a' + from.file()) as char);
+                }
+                if same_file {
+                    san.push((b'1' + from.rank()) as char);
+                }
+            }
+        }
+
+        if mv.is_capture() {
+            san.push('x');
+        }
+        san.push_str(&Square::from_index(mv.to()).to_algebraic());
+
+        if let Some(promotion) = mv.promotion_piece() {
+            san.push('=');
+            san.push(piece_letter(promotion));
+        }
+    }
+
+    let after = board.make_move(mv);
+    if after.in_check() {
+        san.push(if generate_moves(&after).is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+
+    san
+}
+
+// Accepts SAN as people actually type it: check and annotation suffixes,
+// missing or extra capture marks, 0-0 for castling, lowercase piece letters
+// where that can't be confused with a file, e8Q for e8=Q, and long algebraic
+// such as Ng1-f3. A promotion without a piece letter promotes to a queen.
+pub fn parse_san(board: &Board, san: &str) -> Result<Move> {
+    let error = |reason| Error::InvalidMove(san.to_string(), reason);
+
+    let trimmed = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    let trimmed = trimmed.strip_suffix("e.p.").unwrap_or(trimmed);
+    let chars: Vec<char> = trimmed
+        .chars()
+        .filter(|c| !matches!(c, 'x' | ':' | '-' | '=' | ' '))
+        .collect();
+    let moves = generate_moves(board);
+
+    let castle: String = chars.iter().map(|c| c.to_ascii_uppercase()).collect();
+    if castle.chars().all(|c| c == 'O' || c == '0') && (2..=3).contains(&castle.len()) {
+        let kingside = castle.len() == 2;
+        return moves
+            .iter()
+            .copied()
+            .find(|mv| mv.is_castle() && (mv.flags() == FLAG_KING_CASTLE) == kingside)
+            .ok_or(error(MoveError::Illegal));
+    }
+
+    let promotion = match chars.as_slice() {
+        [_, .., '1' | '8', letter] => match piece_from_letter(letter.to_ascii_uppercase()) {
+            Some(PieceType::Pawn | PieceType::King) => None,
+            piece => piece,
+        },
+        _ => None,
+    };
+    let chars = match promotion {
+        Some(_) => &chars[..chars.len() - 1],
+        None => chars.as_slice(),
+    };
+
+    if chars.len() < 2 {
+        return Err(error(MoveError::Syntax));
+    }
+    let target: String = chars[chars.len() - 2..].iter().collect();
+    let target = Square::from_algebraic(&target).map_err(|_| error(MoveError::Syntax))?;
+    let prefix = &chars[..chars.len() - 2];
+
+    // A leading lowercase b is either a pawn on the b-file or a bishop
+    let mut readings: Vec<(PieceType, &[char])> = Vec::with_capacity(2);
+    match prefix.first() {
+        Some(&c) if c.is_ascii_uppercase() => match piece_from_letter(c) {
+            Some(piece) => readings.push((piece, &prefix[1..])),
+            None => return Err(error(MoveError::Syntax)),
+        },
+        Some('b') => {
+            readings.push((PieceType::Pawn, prefix));
+            readings.push((PieceType::Bishop, &prefix[1..]));
+        }
+        Some(&c) if matches!(c, 'n' | 'r' | 'q' | 'k') => {
+            readings.push((
+                piece_from_letter(c.to_ascii_uppercase()).unwrap(),
+                &prefix[1..],
+            ));
+        }
+        _ => readings.push((PieceType::Pawn, prefix)),
+    }
+
+    let mut candidates: Vec<Move> = Vec::new();
+    let mut valid_syntax = false;
+
+    for (piece, qualifier) in readings {
+        let Some((file, rank)) = parse_qualifier(qualifier) else {
+            continue;
+        };
+        valid_syntax = true;
+
+        for mv in moves.iter().copied() {
+            let from = Square::from_index(mv.from());
+            let matches = mv.to() == target.index()
+                && board.piece_at(mv.from()).map(|p| p.0) == Some(piece)
+                && file.is_none_or(|file| file == from.file())
+                && rank.is_none_or(|rank| rank == from.rank())
+                && match promotion {
+                    Some(_) => mv.promotion_piece() == promotion,
+                    None => !mv.is_promotion() || mv.promotion_piece() == Some(PieceType::Queen),
+                };
+            if matches && !candidates.contains(&mv) {
+                candidates.push(mv);
+            }
+        }
+    }
+
+    match candidates.as_slice() {
+        _ if !valid_syntax => Err(error(MoveError::Syntax)),
+        [] => Err(error(MoveError::Illegal)),
+        [mv] => Ok(*mv),
+        _ => Err(error(MoveError::Ambiguous)),
+    }
+}
+
+// Origin file and rank given before the target square, either may be missing
+fn parse_qualifier(qualifier: &[char]) -> Option<(Option<u8>, Option<u8>)> {
+    let mut file = None;
+    let mut rank = None;
+
+    for &c in qualifier {
+        match c {
+            'a'..='h' if file.is_none() && rank.is_none() => file = Some(c as u8 - b'a'),
+            '1'..='8' if rank.is_none() => rank = Some(c as u8 - b'1'),
+            _ => return None,
+        }
+    }
+
+    Some((file, rank))
+}
+
+#[inline(always)]
+fn piece_letter(piece: PieceType) -> char {
+    Piece(piece, Color::White).to_char()
+}
+
+fn piece_from_letter(c: char) -> Option<PieceType> {
+    match Piece::from_char(c) {
+        Some(Piece(piece, Color::White)) => Some(piece),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san_of(fen: &str, uci: &str) -> String {
+        let board = Board::from_fen(fen).unwrap();
+        to_san(&board, Move::from_uci(&board, uci).unwrap())
+    }
+
+    fn uci_of(fen: &str, san: &str) -> Result<String> {
+        let board = Board::from_fen(fen).unwrap();
+        parse_san(&board, san).map(Move::to_uci)
+    }
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn test_to_san() {
+        assert_eq!(san_of(START, "g1f3"), "Nf3");
+        assert_eq!(san_of(START, "e2e4"), "e4");
+        assert_eq!(san_of(KIWIPETE, "e1g1"), "O-O");
+        assert_eq!(san_of(KIWIPETE, "e1c1"), "O-O-O");
+        assert_eq!(san_of(KIWIPETE, "d5e6"), "dxe6");
+        assert_eq!(san_of(KIWIPETE, "f3f6"), "Qxf6");
+        assert_eq!(san_of(KIWIPETE, "e5f7"), "Nxf7");
+
+        let ep = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+        assert_eq!(san_of(ep, "e5d6"), "exd6");
+
+        let promotion = "3r2k1/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(san_of(promotion, "e7e8q"), "e8=Q+");
+        assert_eq!(san_of(promotion, "e7d8n"), "exd8=N");
+        assert_eq!(san_of("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn test_to_san_disambiguation() {
+        // Knights on b1 and f1 reach d2
+        let files = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        assert_eq!(san_of(files, "b1d2"), "Nbd2");
+
+        // Rooks on a1 and a5 share the file
+        let ranks = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san_of(ranks, "a1a3"), "R1a3");
+
+        // Three queens reach e4, the one on h4 shares both a file and a rank
+        let both = "1k6/8/8/8/Q6Q/8/8/4K2Q w - - 0 1";
+        assert_eq!(san_of(both, "h4e4"), "Qh4e4");
+        assert_eq!(san_of(both, "h1e4"), "Q1e4");
+        assert_eq!(san_of(both, "a4e4"), "Qae4");
+    }
+
+    #[test]
+    fn test_parse_san_forgiving() {
+        assert_eq!(uci_of(START, "Nf3").unwrap(), "g1f3");
+        assert_eq!(uci_of(START, "nf3").unwrap(), "g1f3");
+        assert_eq!(uci_of(START, "Ng1-f3").unwrap(), "g1f3");
+        assert_eq!(uci_of(START, "e4!?").unwrap(), "e2e4");
+        assert_eq!(uci_of(START, "b4").unwrap(), "b2b4");
+
+        assert_eq!(uci_of(KIWIPETE, "0-0").unwrap(), "e1g1");
+        assert_eq!(uci_of(KIWIPETE, "O-O-O").unwrap(), "e1c1");
+        assert_eq!(uci_of(KIWIPETE, "de6").unwrap(), "d5e6");
+        assert_eq!(uci_of(KIWIPETE, "Qxf6+").unwrap(), "f3f6");
+        assert_eq!(uci_of(KIWIPETE, "bd3").unwrap(), "e2d3");
+
+        let promotion = "3r2k1/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(uci_of(promotion, "e8=Q+").unwrap(), "e7e8q");
+        assert_eq!(uci_of(promotion, "e8N").unwrap(), "e7e8n");
+        assert_eq!(uci_of(promotion, "exd8=r").unwrap(), "e7d8r");
+        assert_eq!(uci_of(promotion, "e8").unwrap(), "e7e8q");
+
+        let ep = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+        assert_eq!(uci_of(ep, "exd6 e.p.").unwrap(), "e5d6");
+    }
+
+    #[test]
+    fn test_parse_san_errors() {
+        let reason = |fen: &str, san: &str| match uci_of(fen, san) {
+            Err(Error::InvalidMove(_, reason)) => reason,
+            other => panic!("{} gave {:?}", san, other),
+        };
+
+        assert_eq!(reason(START, ""), MoveError::Syntax);
+        assert_eq!(reason(START, "Zf3"), MoveError::Syntax);
+        assert_eq!(reason(START, "Nf9"), MoveError::Syntax);
+        assert_eq!(reason(START, "Nf4"), MoveError::Illegal);
+        assert_eq!(reason(START, "O-O"), MoveError::Illegal);
+        assert_eq!(
+            reason("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "Nd2"),
+            MoveError::Ambiguous
+        );
+    }
+
+    #[test]
+    fn test_san_round_trip() {
+        let fens = [
+            START,
+            KIWIPETE,
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "1k6/8/8/8/Q6Q/8/8/4K2Q w - - 0 1",
+        ];
+
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            for mv in generate_moves(&board).iter() {
+                let san = to_san(&board, *mv);
+                assert_eq!(parse_san(&board, &san).unwrap(), *mv, "{} in {}", san, fen);
+            }
+        }
+    }
+}
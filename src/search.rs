@@ -0,0 +1,1069 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::board::{Board, Color, PieceType};
+use crate::eval::evaluate_position;
+use crate::movegen::{generate_moves, Move};
+use crate::movepick::{HistoryTables, MovePicker};
+use crate::timeman::{TimeManager, DEFAULT_MOVE_OVERHEAD};
+use crate::tt::{Bound, TranspositionTable};
+
+pub const MAX_PLY: usize = 128;
+
+// Checkmate at ply n scores MATE - n for the side delivering it
+pub const MATE: i32 = 100_000;
+pub const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+// Window bound that can be negated without overflowing
+pub const INFINITY: i32 = MATE + 1;
+
+// The stop flag and the clock are polled every this many nodes, must be a power of two
+const STOP_CHECK_INTERVAL: u64 = 2048;
+// How often progress is reported while an iteration runs, currmove is only
+// reported once the search has been running this long
+const INFO_INTERVAL: Duration = Duration::from_secs(1);
+
+#[inline(always)]
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_BOUND
+}
+
+// Full moves until mate, negative when the side to move is getting mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score >= MATE_BOUND {
+        Some((MATE - score + 1) / 2)
+    } else if score <= -MATE_BOUND {
+        Some(-(MATE + score) / 2)
+    } else {
+        None
+    }
+}
+
+// UCI score string, scores are from the engine's point of view
+fn format_score(score: i32) -> String {
+    match mate_in(score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", score),
+    }
+}
+
+// Parameters of a UCI `go` command, times in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    // Search for a mate in this many moves
+    pub mate: Option<u32>,
+    pub infinite: bool,
+    pub ponder: bool,
+    // Restrict the root to these moves, all legal moves when empty
+    pub searchmoves: Vec<Move>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Self::default()
+        }
+    }
+
+    pub fn infinite() -> Self {
+        Self {
+            infinite: true,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PvLine {
+    pub score: i32,
+    pub pv: Vec<Move>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    // From the side to move's point of view
+    pub score: i32,
+    pub depth: u32,
+    // Deepest ply reached including quiescence
+    pub seldepth: u32,
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+    // Best first, one per MultiPV line, the first matches score and pv
+    pub lines: Vec<PvLine>,
+}
+
+pub struct Searcher {
+    tt: Arc<TranspositionTable>,
+    stop: Arc<AtomicBool>,
+    ponder: Arc<AtomicBool>,
+    was_pondering: bool,
+    stopped: bool,
+    nodes: u64,
+    node_limit: u64,
+    seldepth: usize,
+    // Depth of the running iteration, for progress reports
+    depth: u32,
+    start: Instant,
+    last_info: Instant,
+    print_info: bool,
+    // Report castling as king takes rook
+    chess960: bool,
+    time: TimeManager,
+    move_overhead: Duration,
+    threads: usize,
+    // Zero for the main thread, helpers start at staggered depths
+    helper_id: usize,
+    multi_pv: usize,
+    root_moves: Vec<Move>,
+    // Root moves already reported on earlier MultiPV lines of this iteration
+    excluded: Vec<Move>,
+    // Keys of the game positions before the root followed by the current path
+    history: Vec<u64>,
+    // Where the root's own key goes in `history`, earlier keys are from the game
+    root_index: usize,
+    // Triangular principal variation table, indexed by ply
+    pv_table: [[Move; MAX_PLY]; MAX_PLY],
+    pv_length: [usize; MAX_PLY],
+    // Move ordering statistics, kept between searches
+    tables: Box<HistoryTables>,
+    // The move played at each ply of the current path, for counter-moves
+    played: [Option<Move>; MAX_PLY],
+}
+
+impl Searcher {
+    pub fn new() -> Self {
+        Self::with_tt(Arc::new(TranspositionTable::default()))
+    }
+
+    pub fn with_tt(tt: Arc<TranspositionTable>) -> Self {
+        Self {
+            tt,
+            stop: Arc::new(AtomicBool::new(false)),
+            ponder: Arc::new(AtomicBool::new(false)),
+            was_pondering: false,
+            stopped: false,
+            nodes: 0,
+            node_limit: u64::MAX,
+            seldepth: 0,
+            depth: 0,
+            start: Instant::now(),
+            last_info: Instant::now(),
+            print_info: false,
+            chess960: false,
+            time: TimeManager::unlimited(),
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            threads: 1,
+            helper_id: 0,
+            multi_pv: 1,
+            root_moves: Vec::new(),
+            excluded: Vec::new(),
+            history: Vec::new(),
+            root_index: 0,
+            pv_table: [[Move::default(); MAX_PLY]; MAX_PLY],
+            pv_length: [0; MAX_PLY],
+            tables: Box::default(),
+            played: [None; MAX_PLY],
+        }
+    }
+
+    pub fn tt(&self) -> &Arc<TranspositionTable> {
+        &self.tt
+    }
+
+    pub fn set_tt(&mut self, tt: Arc<TranspositionTable>) {
+        self.tt = tt;
+    }
+
+    // Setting this flag from another thread aborts the running search. It is
+    // never cleared by the searcher itself, callers reset it before searching.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    // While set the clock is ignored, clearing it (ponderhit) starts the clock
+    pub fn ponder_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.ponder)
+    }
+
+    // Time reserved per move for GUI and network latency
+    pub fn set_move_overhead(&mut self, move_overhead: Duration) {
+        self.move_overhead = move_overhead;
+    }
+
+    // Helper threads share the hash table with the main thread (Lazy SMP)
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    // Number of best root moves to report, each with its own PV
+    pub fn set_multi_pv(&mut self, multi_pv: usize) {
+        self.multi_pv = multi_pv.max(1);
+    }
+
+    // Zobrist keys of the game positions leading up to the next searched
+    // position, oldest first, so repetitions of the game count as draws
+    pub fn set_history(&mut self, history: &[u64]) {
+        self.history.clear();
+        self.history.extend_from_slice(history);
+    }
+
+    // Forget everything learned about move ordering, for a new game
+    pub fn clear_history_tables(&mut self) {
+        self.tables.clear();
+    }
+
+    // Print UCI info lines to stdout while searching
+    pub fn set_print_info(&mut self, print_info: bool) {
+        self.print_info = print_info;
+    }
+
+    // Castling moves in the info lines use the UCI_Chess960 notation
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    // Iterative deepening until one of the limits is hit or the stop flag is set
+    pub fn search(&mut self, board: &Board, limits: &SearchLimits) -> SearchResult {
+        self.nodes = 0;
+        self.seldepth = 0;
+        self.start = Instant::now();
+        self.last_info = self.start;
+        self.stopped = false;
+        self.was_pondering = false;
+        self.node_limit = limits.nodes.unwrap_or(u64::MAX);
+        self.root_index = self.history.len();
+        self.time = TimeManager::new(limits, board.side_to_move(), self.move_overhead);
+        if self.helper_id == 0 {
+            self.tt.new_search();
+        }
+        self.tables.new_search();
+
+        // Unknown or illegal search moves are ignored rather than searching nothing
+        let legal = generate_moves(board);
+        self.root_moves = limits
+            .searchmoves
+            .iter()
+            .copied()
+            .filter(|mv| legal.iter().any(|legal| legal == mv))
+            .collect();
+
+        // A mate in n moves is found by a 2n - 1 ply search at the latest
+        let max_depth = match (limits.depth, limits.mate) {
+            (Some(depth), _) => depth,
+            (None, Some(mate)) => mate.max(1).saturating_mul(2) - 1,
+            (None, None) => MAX_PLY as u32 - 1,
+        }
+        .clamp(1, MAX_PLY as u32 - 1);
+
+        let root_count = if self.root_moves.is_empty() {
+            legal.len()
+        } else {
+            self.root_moves.len()
+        };
+
+        if self.threads <= 1 {
+            return self.iterate(board, limits, max_depth, root_count);
+        }
+
+        // Helpers search the same position without limits until the main
+        // thread is done, their only output is what they leave in the table
+        let helper_stop = Arc::new(AtomicBool::new(false));
+        let helper_limits = SearchLimits {
+            depth: Some(max_depth),
+            searchmoves: self.root_moves.clone(),
+            ..SearchLimits::default()
+        };
+
+        thread::scope(|scope| {
+            for helper_id in 1..self.threads {
+                let mut helper = Searcher::with_tt(Arc::clone(&self.tt));
+                helper.stop = Arc::clone(&helper_stop);
+                helper.helper_id = helper_id;
+                helper.history = self.history.clone();
+                let limits = &helper_limits;
+                scope.spawn(move || helper.search(board, limits));
+            }
+
+            let result = self.iterate(board, limits, max_depth, root_count);
+            helper_stop.store(true, Ordering::Relaxed);
+            result
+        })
+    }
+
+    fn iterate(
+        &mut self,
+        board: &Board,
+        limits: &SearchLimits,
+        max_depth: u32,
+        root_count: usize,
+    ) -> SearchResult {
+        let mut result = SearchResult::default();
+        let first_depth = (1 + (self.helper_id % 2) as u32).min(max_depth);
+        let lines_wanted = self.multi_pv.min(root_count).max(1);
+        // Moves are made and unmade on this copy as the tree is searched
+        let mut root = *board;
+
+        for depth in first_depth..=max_depth {
+            let iteration_start = Instant::now();
+            self.depth = depth;
+
+            // Each further line is searched with the best moves so far excluded
+            let mut lines = Vec::with_capacity(lines_wanted);
+            let mut score = 0;
+            self.excluded.clear();
+            while lines.len() < lines_wanted {
+                score = self.negamax(&mut root, depth, 0, -INFINITY, INFINITY);
+                if self.stopped || self.pv_length[0] == 0 {
+                    break;
+                }
+                let pv = self.pv_table[0][..self.pv_length[0]].to_vec();
+                self.excluded.push(pv[0]);
+                lines.push(PvLine { score, pv });
+            }
+            self.excluded.clear();
+
+            // An interrupted iteration is only trusted if we have nothing better
+            if self.stopped {
+                if result.best_move.is_none() {
+                    if let Some(line) = lines.first() {
+                        result.pv = line.pv.clone();
+                        result.score = line.score;
+                    } else if self.pv_length[0] > 0 {
+                        result.pv = self.pv_table[0][..self.pv_length[0]].to_vec();
+                    }
+                    result.best_move = result.pv.first().copied();
+                    result.depth = depth;
+                }
+                break;
+            }
+
+            lines.sort_by_key(|line| -line.score);
+            result.pv = lines.first().map_or_else(Vec::new, |line| line.pv.clone());
+            result.best_move = result.pv.first().copied();
+            result.score = lines.first().map_or(score, |line| line.score);
+            result.depth = depth;
+            result.seldepth = self.seldepth as u32;
+            result.nodes = self.nodes;
+            result.lines = lines;
+            score = result.score;
+
+            if self.print_info {
+                for multipv in 0..result.lines.len().max(1) {
+                    println!("{}", self.info_line(&result, multipv));
+                }
+            }
+
+            // No legal moves at the root, deeper iterations won't change anything
+            if result.best_move.is_none() {
+                break;
+            }
+
+            if let (Some(mate), Some(found)) = (limits.mate, mate_in(score)) {
+                if found > 0 && found <= mate as i32 {
+                    break;
+                }
+            }
+
+            if !self.pondering() && !self.time.should_start_iteration(iteration_start.elapsed()) {
+                break;
+            }
+        }
+
+        // Stopped before the first move was searched, any legal move will do
+        if result.best_move.is_none() {
+            result.best_move = generate_moves(board).iter().next().copied();
+            result.pv.extend(result.best_move);
+        }
+        result.nodes = self.nodes;
+
+        // Final counters for the GUI when the last iteration was cut short
+        if self.stopped && self.print_info {
+            println!("{}", self.info_line(&result, 0));
+        }
+
+        result
+    }
+
+    // Tracks ponderhit so the clock only runs from the moment it arrives
+    fn pondering(&mut self) -> bool {
+        if self.ponder.load(Ordering::Relaxed) {
+            self.was_pondering = true;
+            return true;
+        }
+        if self.was_pondering {
+            self.was_pondering = false;
+            self.time.restart();
+        }
+        false
+    }
+
+    #[inline(always)]
+    fn should_stop(&mut self) -> bool {
+        if self.nodes >= self.node_limit {
+            self.stopped = true;
+        } else if self.nodes & (STOP_CHECK_INTERVAL - 1) == 0 {
+            let timed_out = !self.pondering() && self.time.hard_limit_reached();
+            if timed_out || self.stop.load(Ordering::Relaxed) {
+                self.stopped = true;
+            } else if self.print_info && self.last_info.elapsed() >= INFO_INTERVAL {
+                self.last_info = Instant::now();
+                println!("{}", self.progress_line());
+            }
+        }
+        self.stopped
+    }
+
+    // Principal variation search: the first move gets the full window, the
+    // rest a null window, re-searched only if they unexpectedly beat alpha
+    fn negamax(
+        &mut self,
+        board: &mut Board,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.pv_length[ply] = ply;
+
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+
+        if self.should_stop() {
+            return 0;
+        }
+
+        if ply >= MAX_PLY - 1 {
+            return evaluate(board);
+        }
+
+        // Mating material is only worth checking once few pieces are left
+        if ply > 0
+            && (board.is_fifty_move_draw()
+                || self.is_repetition(board)
+                || board.all_occupancy().count_ones() <= 4 && board.is_insufficient_material())
+        {
+            return 0;
+        }
+
+        let pv_node = beta - alpha > 1;
+        let tt_entry = self.tt.probe(board.hash, ply);
+        if let Some(entry) = tt_entry {
+            // Cutting at PV nodes would truncate the principal variation
+            if !pv_node && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return entry.score,
+                    Bound::Lower if entry.score >= beta => return entry.score,
+                    Bound::Upper if entry.score <= alpha => return entry.score,
+                    _ => {}
+                }
+            }
+        }
+
+        let prev = ply.checked_sub(1).and_then(|prev| self.played[prev]);
+        let mut picker = MovePicker::new(
+            board,
+            tt_entry.and_then(|entry| entry.best_move),
+            self.tables.killers(ply),
+            self.tables.counter(prev),
+        );
+
+        let alpha_orig = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        let mut legal_moves = 0;
+        let mut searched = 0;
+        let mut quiets_tried = Vec::new();
+
+        while let Some(mv) = picker.next(board, &self.tables) {
+            legal_moves += 1;
+            if ply == 0
+                && (!self.root_moves.is_empty() && !self.root_moves.contains(&mv)
+                    || self.excluded.contains(&mv))
+            {
+                continue;
+            }
+
+            if ply == 0 && self.print_info && self.start.elapsed() >= INFO_INTERVAL {
+                println!(
+                    "info depth {} currmove {} currmovenumber {}",
+                    depth,
+                    mv.to_uci_with(self.chess960),
+                    searched + 1
+                );
+            }
+
+            self.history.push(board.hash);
+            self.played[ply] = Some(mv);
+            let undo = board.make_move_mut(mv);
+
+            let mut score;
+            if searched == 0 {
+                score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha);
+            } else {
+                score = -self.negamax(board, depth - 1, ply + 1, -alpha - 1, -alpha);
+                if score > alpha && score < beta {
+                    score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha);
+                }
+            }
+            searched += 1;
+
+            board.unmake_move(mv, undo);
+            self.history.pop();
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+
+                if score > alpha {
+                    alpha = score;
+                    best_move = Some(mv);
+                    self.update_pv(ply, mv);
+
+                    if alpha >= beta {
+                        if is_quiet(mv) {
+                            let us = board.side_to_move();
+                            self.tables
+                                .update_quiets(us, ply, prev, mv, &quiets_tried, depth);
+                        }
+                        break;
+                    }
+                }
+            }
+
+            if is_quiet(mv) {
+                quiets_tried.push(mv);
+            }
+        }
+
+        if legal_moves == 0 {
+            return if picker.in_check() {
+                -MATE + ply as i32
+            } else {
+                0
+            };
+        }
+
+        // The root score of a later MultiPV line isn't the score of the position
+        if ply == 0 && !self.excluded.is_empty() {
+            return best_score;
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if alpha > alpha_orig {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt
+            .store(board.hash, depth, bound, best_score, ply, best_move);
+
+        best_score
+    }
+
+    // Extends leaf nodes through captures and queen promotions until the position is quiet
+    fn quiescence(&mut self, board: &mut Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+
+        if self.should_stop() {
+            return 0;
+        }
+
+        if ply >= MAX_PLY - 1 {
+            return evaluate(board);
+        }
+
+        let mut picker = MovePicker::quiescence(board);
+        let in_check = picker.in_check();
+
+        // Standing pat is not an option when in check, all evasions are searched
+        let mut best_score = if in_check {
+            -INFINITY
+        } else {
+            let stand_pat = evaluate(board);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            stand_pat
+        };
+
+        let mut legal_moves = 0;
+        while let Some(mv) = picker.next(board, &self.tables) {
+            legal_moves += 1;
+
+            // Underpromotions are never better than promoting to a queen here
+            if !in_check && mv.is_promotion() && mv.promotion_piece() != Some(PieceType::Queen) {
+                continue;
+            }
+
+            let undo = board.make_move_mut(mv);
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move(mv, undo);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if in_check && legal_moves == 0 {
+            return -MATE + ply as i32;
+        }
+
+        best_score
+    }
+
+    // A single repetition of a position inside the tree is scored as a draw,
+    // the side that could avoid it will have found something better
+    // elsewhere. Positions from the game before the root need a threefold.
+    fn is_repetition(&self, board: &Board) -> bool {
+        // Nothing before the last capture or pawn move can repeat
+        let end = self.history.len();
+        let start = end - (board.halfmove as usize).min(end);
+        let mut earlier = 0;
+
+        for idx in (start..end).rev().skip(1).step_by(2) {
+            if self.history[idx] == board.hash {
+                if idx >= self.root_index {
+                    return true;
+                }
+                earlier += 1;
+                if earlier == 2 {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn info_line(&self, result: &SearchResult, multipv: usize) -> String {
+        let (score, pv) = result
+            .lines
+            .get(multipv)
+            .map_or((result.score, &result.pv), |line| (line.score, &line.pv));
+        let pv: Vec<String> = pv.iter().map(|mv| mv.to_uci_with(self.chess960)).collect();
+        let multipv = if self.multi_pv > 1 {
+            format!(" multipv {}", multipv + 1)
+        } else {
+            String::new()
+        };
+
+        format!(
+            "info depth {} seldepth {}{} score {} {} pv {}",
+            result.depth,
+            result.seldepth,
+            multipv,
+            format_score(score),
+            self.counters(),
+            pv.join(" ")
+        )
+    }
+
+    fn progress_line(&self) -> String {
+        format!(
+            "info depth {} seldepth {} {}",
+            self.depth,
+            self.seldepth,
+            self.counters()
+        )
+    }
+
+    fn counters(&self) -> String {
+        let time = self.start.elapsed().as_millis() as u64;
+        let nps = self.nodes * 1000 / time.max(1);
+        format!(
+            "nodes {} nps {} time {} hashfull {}",
+            self.nodes,
+            nps,
+            time,
+            self.tt.hashfull()
+        )
+    }
+
+    fn update_pv(&mut self, ply: usize, mv: Move) {
+        let child_len = self.pv_length[ply + 1];
+        self.pv_table[ply][ply] = mv;
+        for i in ply + 1..child_len {
+            self.pv_table[ply][i] = self.pv_table[ply + 1][i];
+        }
+        self.pv_length[ply] = child_len.max(ply + 1);
+    }
+}
+
+// Static evaluation from the side to move's point of view
+#[inline(always)]
+fn evaluate(board: &Board) -> i32 {
+    match board.side_to_move() {
+        Color::White => evaluate_position(board),
+        Color::Black => -evaluate_position(board),
+    }
+}
+
+// Quiet moves are the ones the history tables learn about
+#[inline(always)]
+fn is_quiet(mv: Move) -> bool {
+    !mv.is_capture() && mv.promotion_piece() != Some(PieceType::Queen)
+}
+
+impl Default for Searcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_reaches_depth() {
+        let board = Board::default();
+        let mut searcher = Searcher::new();
+        let result = searcher.search(&board, &SearchLimits::depth(3));
+
+        assert_eq!(result.depth, 3);
+        assert!(result.best_move.is_some());
+        assert!(result.nodes > 0);
+    }
+
+    #[test]
+    fn test_seldepth_includes_quiescence() {
+        // Plenty of captures available, quiescence goes past the nominal depth
+        let board =
+            Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/3PP3/5N2/PPP2PPP/RNBQKB1R b KQkq - 0 3")
+                .unwrap();
+        let mut searcher = Searcher::new();
+        let result = searcher.search(&board, &SearchLimits::depth(2));
+
+        assert!(result.seldepth > result.depth);
+    }
+
+    #[test]
+    fn test_info_line() {
+        let board = Board::default();
+        let mut searcher = Searcher::new();
+        let result = searcher.search(&board, &SearchLimits::depth(2));
+        let line = searcher.info_line(&result, 0);
+
+        let pv: Vec<String> = result.pv.iter().map(|mv| mv.to_uci()).collect();
+        let prefix = format!(
+            "info depth 2 seldepth {} score {} nodes {} nps ",
+            result.seldepth,
+            format_score(result.score),
+            searcher.nodes
+        );
+        let suffix = format!(" hashfull {} pv {}", searcher.tt.hashfull(), pv.join(" "));
+        assert!(line.starts_with(&prefix), "{}", line);
+        assert!(line.ends_with(&suffix), "{}", line);
+
+        // Only the timings vary between runs
+        let timings: Vec<&str> = line[prefix.len()..line.len() - suffix.len()]
+            .split(' ')
+            .collect();
+        assert_eq!(timings.len(), 3, "{}", line);
+        assert!(timings[0].parse::<u64>().is_ok());
+        assert_eq!(timings[1], "time");
+        assert!(timings[2].parse::<u64>().is_ok());
+        assert_eq!(result.pv.len(), 2);
+    }
+
+    #[test]
+    fn test_pv_starts_with_best_move() {
+        let board = Board::default();
+        let mut searcher = Searcher::new();
+        let result = searcher.search(&board, &SearchLimits::depth(3));
+
+        // Hash cutoffs may truncate the PV, but never below the root move
+        assert!(!result.pv.is_empty() && result.pv.len() <= 3);
+        assert_eq!(result.pv.first().copied(), result.best_move);
+    }
+
+    #[test]
+    fn test_finds_mate_in_one() {
+        // Back rank mate: Ra1-a8#
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut searcher = Searcher::new();
+        let result = searcher.search(&board, &SearchLimits::depth(2));
+
+        assert_eq!(result.best_move.unwrap().to_uci(), "a1a8");
+        assert_eq!(result.score, MATE - 1);
+    }
+
+    #[test]
+    fn test_no_moves_returns_none() {
+        // Black is checkmated
+        let board = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        let mut searcher = Searcher::new();
+        let result = searcher.search(&board, &SearchLimits::depth(3));
+
+        assert!(result.best_move.is_none());
+        assert!(result.pv.is_empty());
+    }
+
+    #[test]
+    fn test_quiescence_sees_recapture() {
+        // Qxd5 wins a pawn at depth 1 but loses the queen to exd5
+        let board = Board::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let mut searcher = Searcher::new();
+        let result = searcher.search(&board, &SearchLimits::depth(1));
+
+        assert_ne!(result.best_move.unwrap().to_uci(), "d1d5");
+        assert!(result.score > 0);
+    }
+
+    #[test]
+    fn test_score_is_side_relative() {
+        // Same material edge for the side to move regardless of color
+        let white = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let black = Board::from_fen("3qk3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        let mut searcher = Searcher::new();
+
+        assert!(searcher.search(&white, &SearchLimits::depth(2)).score > 500);
+        assert!(searcher.search(&black, &SearchLimits::depth(2)).score > 500);
+    }
+
+    #[test]
+    fn test_finds_mate_in_two() {
+        // 1. Kb6 Kb8 2. Rh8#
+        let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        let mut searcher = Searcher::new();
+        let result = searcher.search(&board, &SearchLimits::depth(4));
+
+        assert_eq!(result.score, MATE - 3);
+        assert_eq!(mate_in(result.score), Some(2));
+    }
+
+    #[test]
+    fn test_mated_score() {
+        // 1... Kb8 2. Rh8#
+        let board = Board::from_fen("k7/8/1K6/8/8/8/8/7R b - - 0 1").unwrap();
+        let mut searcher = Searcher::new();
+        let result = searcher.search(&board, &SearchLimits::depth(3));
+
+        assert_eq!(mate_in(result.score), Some(-1));
+    }
+
+    #[test]
+    fn test_mate_in_conversion() {
+        assert_eq!(mate_in(MATE - 1), Some(1));
+        assert_eq!(mate_in(MATE - 2), Some(1));
+        assert_eq!(mate_in(MATE - 3), Some(2));
+        assert_eq!(mate_in(-MATE + 2), Some(-1));
+        assert_eq!(mate_in(-MATE), Some(0));
+        assert_eq!(mate_in(250), None);
+        assert!(!is_mate_score(-250));
+    }
+
+    #[test]
+    fn test_format_score() {
+        assert_eq!(format_score(35), "cp 35");
+        assert_eq!(format_score(-120), "cp -120");
+        assert_eq!(format_score(MATE - 1), "mate 1");
+        assert_eq!(format_score(MATE - 5), "mate 3");
+        assert_eq!(format_score(-MATE + 4), "mate -2");
+    }
+
+    #[test]
+    fn test_stop_flag_still_returns_a_move() {
+        let board = Board::default();
+        let mut searcher = Searcher::new();
+        searcher.stop_flag().store(true, Ordering::Relaxed);
+        let result = searcher.search(&board, &SearchLimits::depth(64));
+
+        let legal = generate_moves(&board);
+        assert!(legal.iter().any(|&mv| Some(mv) == result.best_move));
+        assert!(result.depth < 64);
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let board = Board::default();
+        let mut searcher = Searcher::new();
+        let limits = SearchLimits {
+            nodes: Some(5000),
+            ..SearchLimits::default()
+        };
+        let result = searcher.search(&board, &limits);
+
+        assert!(result.nodes <= 5000);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn test_movetime_limit() {
+        let board = Board::default();
+        let mut searcher = Searcher::new();
+        let limits = SearchLimits {
+            movetime: Some(Duration::from_millis(50)),
+            ..SearchLimits::default()
+        };
+        let start = Instant::now();
+        let result = searcher.search(&board, &limits);
+
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn test_mate_limit_stops_when_found() {
+        let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        let mut searcher = Searcher::new();
+        let limits = SearchLimits {
+            mate: Some(2),
+            ..SearchLimits::default()
+        };
+        let result = searcher.search(&board, &limits);
+
+        assert_eq!(mate_in(result.score), Some(2));
+        assert!(result.depth <= 4);
+    }
+
+    #[test]
+    fn test_multi_pv_reports_distinct_lines() {
+        let board = Board::default();
+        let mut searcher = Searcher::new();
+        searcher.set_multi_pv(3);
+        let result = searcher.search(&board, &SearchLimits::depth(3));
+
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!(result.lines[0].pv, result.pv);
+        assert_eq!(result.lines[0].score, result.score);
+        assert!(result.lines.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let mut first_moves: Vec<Move> = result.lines.iter().map(|line| line.pv[0]).collect();
+        first_moves.dedup();
+        assert_eq!(first_moves.len(), 3);
+    }
+
+    #[test]
+    fn test_multi_pv_limited_by_legal_moves() {
+        // Kb8 is the only legal move
+        let board = Board::from_fen("k7/8/1K6/8/8/8/8/7R b - - 0 1").unwrap();
+        let mut searcher = Searcher::new();
+        searcher.set_multi_pv(5);
+        let result = searcher.search(&board, &SearchLimits::depth(2));
+
+        assert_eq!(result.lines.len(), 1);
+    }
+
+    #[test]
+    fn test_helper_threads_agree_on_mate() {
+        let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        let mut searcher = Searcher::new();
+        searcher.set_threads(4);
+        let result = searcher.search(&board, &SearchLimits::depth(4));
+
+        assert_eq!(result.score, MATE - 3);
+        assert_eq!(result.depth, 4);
+    }
+
+    #[test]
+    fn test_repetition_scored_as_draw() {
+        let mut board = Board::default();
+        let mut history = Vec::new();
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"] {
+            let mv = *generate_moves(&board)
+                .iter()
+                .find(|mv| mv.to_uci() == uci)
+                .unwrap();
+            history.push(board.hash);
+            board = board.make_move(mv);
+        }
+        let limits = SearchLimits {
+            depth: Some(2),
+            searchmoves: generate_moves(&board)
+                .iter()
+                .copied()
+                .filter(|mv| mv.to_uci() == "g1f3")
+                .collect(),
+            ..SearchLimits::default()
+        };
+
+        let mut searcher = Searcher::new();
+        assert_ne!(searcher.search(&board, &limits).score, 0);
+
+        // After one round of knight moves Nf3 only repeats a game position once
+        let mut searcher = Searcher::new();
+        searcher.set_history(&history[..4]);
+        assert_ne!(searcher.search(&board, &limits).score, 0);
+
+        // After two rounds it is the third time
+        let mut searcher = Searcher::new();
+        searcher.set_history(&history);
+        assert_eq!(searcher.search(&board, &limits).score, 0);
+    }
+
+    #[test]
+    fn test_fifty_move_rule_scored_as_draw() {
+        let board = Board::from_fen("k7/8/8/8/8/8/8/K5Q1 w - - 0 60").unwrap();
+        let mut searcher = Searcher::new();
+        assert!(searcher.search(&board, &SearchLimits::depth(2)).score > 500);
+
+        // Every move reaches the hundredth half-move without a capture
+        let board = Board::from_fen("k7/8/8/8/8/8/8/K5Q1 w - - 99 60").unwrap();
+        let mut searcher = Searcher::new();
+        assert_eq!(searcher.search(&board, &SearchLimits::depth(2)).score, 0);
+    }
+
+    #[test]
+    fn test_searchmoves_restricts_root() {
+        let board = Board::default();
+        let h3 = generate_moves(&board)
+            .iter()
+            .copied()
+            .find(|mv| mv.to_uci() == "h2h3")
+            .unwrap();
+        let mut searcher = Searcher::new();
+        let limits = SearchLimits {
+            depth: Some(3),
+            searchmoves: vec![h3],
+            ..SearchLimits::default()
+        };
+        let result = searcher.search(&board, &limits);
+
+        assert_eq!(result.best_move, Some(h3));
+    }
+
+    #[test]
+    fn test_tt_reduces_nodes_on_research() {
+        let board =
+            Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 1")
+                .unwrap();
+        let mut searcher = Searcher::new();
+        let first = searcher.search(&board, &SearchLimits::depth(4));
+        let second = searcher.search(&board, &SearchLimits::depth(4));
+
+        assert!(second.nodes < first.nodes);
+        assert_eq!(first.best_move, second.best_move);
+    }
+}
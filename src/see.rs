@@ -0,0 +1,244 @@
+use crate::board::{Board, Color, PieceType};
+use crate::eval::piece_value;
+use crate::movegen::{attackers_to, Move, FLAG_EP_CAPTURE};
+
+// Static exchange evaluation: the material balance for the side to move
+// after `mv` and the best sequence of recaptures on its destination square,
+// where either side may stop capturing whenever that is better for it.
+// Attackers hidden behind others join in as the pieces in front leave, pins
+// are ignored. Quiet moves score what is lost if the piece is taken.
+pub fn see(board: &Board, mv: Move) -> i32 {
+    if mv.is_castle() {
+        return 0;
+    }
+
+    let mut gains = [0; 32];
+    let mut depth = 0;
+    let to = mv.to();
+    let (mut gain, mut occ, mut on_square) = first_capture(board, mv);
+    gains[0] = gain;
+    let mut side = board.side_to_move().opposite();
+
+    while let Some((sq, piece_type)) = least_valuable_attacker(board, to, occ, side) {
+        // The king can only take last, when nothing defends the square
+        if piece_type == PieceType::King {
+            let defenders = attackers_to(board, to, occ) & occ & board.occupancy(side.opposite());
+            if defenders != 0 {
+                break;
+            }
+        }
+
+        let (promotion, value) = recapture_value(piece_type, to);
+        gain = on_square + promotion - gain;
+        depth += 1;
+        gains[depth] = gain;
+        on_square = value;
+        occ &= !(1u64 << sq);
+        side = side.opposite();
+    }
+
+    // Each side only continues the exchange if that beats stopping
+    while depth > 0 {
+        gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+        depth -= 1;
+    }
+
+    gains[0]
+}
+
+// Whether the exchange started by `mv` wins at least `threshold`. Most calls
+// are decided by the first capture alone, before any recapture is looked at.
+pub fn see_ge(board: &Board, mv: Move, threshold: i32) -> bool {
+    if mv.is_castle() {
+        return threshold <= 0;
+    }
+
+    let (gain, _, on_square) = first_capture(board, mv);
+    if gain < threshold {
+        return false;
+    }
+    // Losing the moving piece for nothing still clears the threshold, unless
+    // a recapture can promote and take even more
+    if gain - on_square >= threshold && !is_promotion_rank(mv.to()) {
+        return true;
+    }
+
+    see(board, mv) >= threshold
+}
+
+// Material won by the move itself, the occupancy after it and the value of
+// the piece it leaves on the destination square
+fn first_capture(board: &Board, mv: Move) -> (i32, u64, i32) {
+    let from = mv.from();
+    let to = mv.to();
+    let moving = board
+        .piece_at(from)
+        .map_or(PieceType::Pawn, |piece| piece.0);
+    let mut occ = board.all_occupancy() & !(1u64 << from);
+
+    let captured = if mv.flags() == FLAG_EP_CAPTURE {
+        let captured_sq = match board.side_to_move() {
+            Color::White => to - 8,
+            Color::Black => to + 8,
+        };
+        occ &= !(1u64 << captured_sq);
+        piece_value(PieceType::Pawn)
+    } else {
+        board.piece_at(to).map_or(0, |piece| piece_value(piece.0))
+    };
+
+    match mv.promotion_piece() {
+        Some(promotion) => {
+            let value = piece_value(promotion);
+            (captured + value - piece_value(PieceType::Pawn), occ, value)
+        }
+        None => (captured, occ, piece_value(moving)),
+    }
+}
+
+// Extra material a recapture gains by promoting, and the value of the piece
+// it leaves on the square. Pawns recapturing on the last rank always queen.
+fn recapture_value(piece_type: PieceType, to: usize) -> (i32, i32) {
+    if piece_type == PieceType::Pawn && is_promotion_rank(to) {
+        let queen = piece_value(PieceType::Queen);
+        (queen - piece_value(PieceType::Pawn), queen)
+    } else {
+        (0, piece_value(piece_type))
+    }
+}
+
+#[inline(always)]
+fn is_promotion_rank(sq: usize) -> bool {
+    !(8..56).contains(&sq)
+}
+
+fn least_valuable_attacker(
+    board: &Board,
+    to: usize,
+    occ: u64,
+    side: Color,
+) -> Option<(usize, PieceType)> {
+    let attackers = attackers_to(board, to, occ) & occ & board.occupancy(side);
+    if attackers == 0 {
+        return None;
+    }
+
+    [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+        PieceType::King,
+    ]
+    .into_iter()
+    .find_map(|piece_type| {
+        let pieces = attackers & board.pieces(piece_type, side);
+        (pieces != 0).then(|| (pieces.trailing_zeros() as usize, piece_type))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAWN: i32 = piece_value(PieceType::Pawn);
+    const KNIGHT: i32 = piece_value(PieceType::Knight);
+    const BISHOP: i32 = piece_value(PieceType::Bishop);
+    const ROOK: i32 = piece_value(PieceType::Rook);
+    const QUEEN: i32 = piece_value(PieceType::Queen);
+
+    // (FEN, move, expected SEE)
+    const SEE_CASES: &[(&str, &str, i32)] = &[
+        // Undefended pawn
+        (
+            "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1",
+            "e1e5",
+            PAWN,
+        ),
+        // Long exchange with x-rays behind the rook and the bishop
+        (
+            "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+            "d3e5",
+            PAWN - KNIGHT,
+        ),
+        // Pawn takes a defended knight
+        ("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1", "e4d5", KNIGHT - PAWN),
+        // Rook takes a rook defended by the king, the rook behind makes the
+        // recapture illegal
+        ("4k3/4r3/8/8/8/8/4R3/4R1K1 w - - 0 1", "e2e7", ROOK),
+        ("4k3/4r3/8/8/8/8/4R3/6K1 w - - 0 1", "e2e7", 0),
+        // Queen takes a pawn defended by a pawn
+        ("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", "d1d5", PAWN - QUEEN),
+        // The queen behind the bishop only joins in after it
+        (
+            "4k3/8/2p5/3p4/8/1B6/Q7/4K3 w - - 0 1",
+            "b3d5",
+            PAWN - BISHOP + PAWN,
+        ),
+        // Black side, knight takes a bishop defended twice
+        (
+            "4k3/8/8/2n5/8/3B4/2P1P3/4K3 b - - 0 1",
+            "c5d3",
+            BISHOP - KNIGHT,
+        ),
+        // En passant, undefended and defended
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", PAWN),
+        ("4k3/2p5/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 0),
+        // Promotions, the captured rook and the new queen minus the pawn
+        (
+            "3r3k/4P3/8/8/8/8/8/4K3 w - - 0 1",
+            "e7d8q",
+            ROOK + QUEEN - PAWN,
+        ),
+        ("2kr4/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7d8q", ROOK - PAWN),
+        (
+            "2kr4/4P3/8/8/8/8/8/4K3 w - - 0 1",
+            "e7d8n",
+            ROOK + KNIGHT - PAWN - KNIGHT,
+        ),
+        ("7k/4P3/8/8/8/8/8/K7 w - - 0 1", "e7e8q", QUEEN - PAWN),
+        (
+            "4k3/8/8/8/8/8/3p1K2/4N3 b - - 0 1",
+            "d2e1q",
+            KNIGHT + QUEEN - PAWN - QUEEN,
+        ),
+        // A recapturing pawn promotes as well
+        (
+            "4k3/8/8/8/8/8/3p3K/4n2R w - - 0 1",
+            "h1e1",
+            KNIGHT - ROOK - (QUEEN - PAWN),
+        ),
+        // Quiet moves to attacked and safe squares
+        ("4k3/8/2p5/8/8/2N5/8/4K3 w - - 0 1", "c3d5", -KNIGHT),
+        ("4k3/8/8/8/8/2N5/8/4K3 w - - 0 1", "c3d5", 0),
+        ("r3k3/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1", 0),
+    ];
+
+    #[test]
+    fn test_see() {
+        for &(fen, uci, expected) in SEE_CASES {
+            let board = Board::from_fen(fen).unwrap();
+            let mv = Move::from_uci(&board, uci).unwrap();
+            assert_eq!(see(&board, mv), expected, "{} {}", fen, uci);
+        }
+    }
+
+    #[test]
+    fn test_see_ge_agrees_with_see() {
+        for &(fen, uci, expected) in SEE_CASES {
+            let board = Board::from_fen(fen).unwrap();
+            let mv = Move::from_uci(&board, uci).unwrap();
+            for threshold in [expected - 1, expected, expected + 1, 0, -PAWN, PAWN] {
+                assert_eq!(
+                    see_ge(&board, mv, threshold),
+                    expected >= threshold,
+                    "{} {} {}",
+                    fen,
+                    uci,
+                    threshold
+                );
+            }
+        }
+    }
+}
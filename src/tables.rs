@@ -23,6 +23,9 @@ pub const RANK_6: u64 = 0x0000FF0000000000;
 pub const RANK_7: u64 = 0x00FF000000000000;
 pub const RANK_8: u64 = 0xFF00000000000000;
 
+pub const DARK_SQUARES: u64 = 0xAA55AA55AA55AA55;
+pub const LIGHT_SQUARES: u64 = !DARK_SQUARES;
+
 // Direction indices for RAYS array
 pub const DIR_N: usize = 0;
 pub const DIR_NE: usize = 1;
@@ -279,7 +282,7 @@ const fn generate_rays() -> [[u64; 64]; 8] {
 
 // Between table - squares between sq1 and sq2 (exclusive)
 // Only filled for squares on the same rank, file, or diagonal
-pub const BETWEEN: [[u64; 64]; 64] = generate_between();
+pub static BETWEEN: [[u64; 64]; 64] = generate_between();
 
 const fn generate_between() -> [[u64; 64]; 64] {
     let mut between = [[0u64; 64]; 64];
@@ -330,7 +333,7 @@ const fn generate_between() -> [[u64; 64]; 64] {
 
 // Line table - full line through sq1 and sq2
 // Only filled for squares on the same rank, file, or diagonal
-pub const LINE: [[u64; 64]; 64] = generate_line();
+pub static LINE: [[u64; 64]; 64] = generate_line();
 
 const fn generate_line() -> [[u64; 64]; 64] {
     let mut line = [[0u64; 64]; 64];
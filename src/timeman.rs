@@ -0,0 +1,195 @@
+use std::time::{Duration, Instant};
+
+use crate::board::Color;
+use crate::search::SearchLimits;
+
+pub const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(10);
+
+// Assumed number of moves left when the GUI doesn't send movestogo
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+// The next iteration is assumed to take this many times longer than the last one
+const BRANCHING_ESTIMATE: u32 = 2;
+
+pub struct TimeManager {
+    start: Instant,
+    // Don't start another iteration after this much time
+    soft: Option<Duration>,
+    // Abort the running iteration after this much time
+    hard: Option<Duration>,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, side: Color, move_overhead: Duration) -> Self {
+        let (soft, hard) = match limits.movetime {
+            Some(movetime) => {
+                let budget = movetime.saturating_sub(move_overhead);
+                (Some(budget), Some(budget))
+            }
+            None if limits.infinite => (None, None),
+            None => {
+                let (time, inc) = match side {
+                    Color::White => (limits.wtime, limits.winc),
+                    Color::Black => (limits.btime, limits.binc),
+                };
+                match time {
+                    Some(time) => {
+                        let (soft, hard) = allocate(time, inc, limits.movestogo, move_overhead);
+                        (Some(soft), Some(hard))
+                    }
+                    None => (None, None),
+                }
+            }
+        };
+
+        Self {
+            start: Instant::now(),
+            soft,
+            hard,
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self {
+            start: Instant::now(),
+            soft: None,
+            hard: None,
+        }
+    }
+
+    // The clock starts over on ponderhit, thinking time spent pondering is free
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn soft_limit(&self) -> Option<Duration> {
+        self.soft
+    }
+
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        self.hard.is_some_and(|hard| self.elapsed() >= hard)
+    }
+
+    // Whether another iteration is worth starting given how long the last one took
+    pub fn should_start_iteration(&self, last_iteration: Duration) -> bool {
+        let elapsed = self.elapsed();
+
+        if self.soft.is_some_and(|soft| elapsed >= soft) {
+            return false;
+        }
+
+        let predicted = elapsed + last_iteration * BRANCHING_ESTIMATE;
+        self.hard.is_none_or(|hard| predicted < hard)
+    }
+}
+
+// Splits the remaining clock time into soft and hard budgets for this move
+fn allocate(
+    time: Duration,
+    inc: Option<Duration>,
+    movestogo: Option<u32>,
+    move_overhead: Duration,
+) -> (Duration, Duration) {
+    let remaining = time.saturating_sub(move_overhead);
+    let inc = inc.unwrap_or(Duration::ZERO);
+    let moves_to_go = movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, 50);
+
+    // Never plan to use more than 80% of what is left on the clock
+    let max = remaining * 4 / 5;
+    let soft = (remaining / moves_to_go + inc * 3 / 4).min(max);
+    let hard = (soft * 3).min(max);
+
+    (soft, hard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_movetime_is_fixed() {
+        let limits = SearchLimits {
+            movetime: Some(ms(1000)),
+            ..SearchLimits::default()
+        };
+        let tm = TimeManager::new(&limits, Color::White, ms(50));
+
+        assert_eq!(tm.soft_limit(), Some(ms(950)));
+        assert_eq!(tm.hard_limit(), Some(ms(950)));
+    }
+
+    #[test]
+    fn test_no_clock_is_unlimited() {
+        let tm = TimeManager::new(&SearchLimits::depth(5), Color::White, ms(10));
+        assert_eq!(tm.soft_limit(), None);
+        assert_eq!(tm.hard_limit(), None);
+        assert!(!tm.hard_limit_reached());
+        assert!(tm.should_start_iteration(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn test_uses_own_clock() {
+        let limits = SearchLimits {
+            wtime: Some(ms(60_000)),
+            btime: Some(ms(6_000)),
+            ..SearchLimits::default()
+        };
+        let white = TimeManager::new(&limits, Color::White, ms(0));
+        let black = TimeManager::new(&limits, Color::Black, ms(0));
+
+        assert_eq!(white.soft_limit(), Some(ms(2_000)));
+        assert_eq!(black.soft_limit(), Some(ms(200)));
+        assert!(white.hard_limit() > white.soft_limit());
+    }
+
+    #[test]
+    fn test_increment_and_movestogo() {
+        let limits = SearchLimits {
+            btime: Some(ms(10_000)),
+            binc: Some(ms(1_000)),
+            movestogo: Some(10),
+            ..SearchLimits::default()
+        };
+        let tm = TimeManager::new(&limits, Color::Black, ms(0));
+
+        assert_eq!(tm.soft_limit(), Some(ms(1_750)));
+        assert_eq!(tm.hard_limit(), Some(ms(5_250)));
+    }
+
+    #[test]
+    fn test_never_exceeds_remaining_time() {
+        let limits = SearchLimits {
+            wtime: Some(ms(100)),
+            winc: Some(ms(5_000)),
+            movestogo: Some(1),
+            ..SearchLimits::default()
+        };
+        let tm = TimeManager::new(&limits, Color::White, ms(20));
+
+        assert_eq!(tm.soft_limit(), Some(ms(64)));
+        assert_eq!(tm.hard_limit(), Some(ms(64)));
+    }
+
+    #[test]
+    fn test_should_start_iteration() {
+        let limits = SearchLimits {
+            movetime: Some(ms(1_000)),
+            ..SearchLimits::default()
+        };
+        let tm = TimeManager::new(&limits, Color::White, ms(0));
+
+        assert!(tm.should_start_iteration(ms(10)));
+        assert!(!tm.should_start_iteration(ms(600)));
+    }
+}
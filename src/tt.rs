@@ -0,0 +1,294 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::movegen::Move;
+use crate::search::MATE_BOUND;
+
+pub const DEFAULT_HASH_MB: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtEntry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+}
+
+// Entries are stored as (key ^ data, data) so that a torn write from a
+// concurrent searcher fails the key check instead of returning garbage.
+//
+// data layout:
+//   bits  0..32  score
+//   bits 32..48  best move
+//   bits 48..56  depth
+//   bits 56..58  bound (never zero, so an empty slot has data == 0)
+//   bits 58..64  generation
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+// Slot 0 is depth-preferred, slot 1 is always-replace
+#[derive(Default)]
+struct Bucket {
+    slots: [Slot; 2],
+}
+
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub fn new(mb: usize) -> Self {
+        let count = (mb.max(1) * 1024 * 1024 / std::mem::size_of::<Bucket>()).max(1);
+        let mut buckets = Vec::with_capacity(count);
+        buckets.resize_with(count, Bucket::default);
+
+        Self {
+            buckets,
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    pub fn clear(&self) {
+        for bucket in &self.buckets {
+            for slot in &bucket.slots {
+                slot.key.store(0, Ordering::Relaxed);
+                slot.data.store(0, Ordering::Relaxed);
+            }
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    // Called once per search so that entries from older searches get replaced first
+    pub fn new_search(&self) {
+        let next = (self.generation.load(Ordering::Relaxed) + 1) & 0x3F;
+        self.generation.store(next, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    fn bucket(&self, key: u64) -> &Bucket {
+        let idx = ((key as u128 * self.buckets.len() as u128) >> 64) as usize;
+        &self.buckets[idx]
+    }
+
+    pub fn probe(&self, key: u64, ply: usize) -> Option<TtEntry> {
+        for slot in &self.bucket(key).slots {
+            let data = slot.data.load(Ordering::Relaxed);
+            if data != 0 && slot.key.load(Ordering::Relaxed) ^ data == key {
+                return Some(unpack(data, ply));
+            }
+        }
+
+        None
+    }
+
+    pub fn store(
+        &self,
+        key: u64,
+        depth: u32,
+        bound: Bound,
+        score: i32,
+        ply: usize,
+        best_move: Option<Move>,
+    ) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let bucket = self.bucket(key);
+        let preferred = &bucket.slots[0];
+
+        let old = preferred.data.load(Ordering::Relaxed);
+        let old_key = preferred.key.load(Ordering::Relaxed) ^ old;
+        let old_depth = ((old >> 48) & 0xFF) as u32;
+        let old_generation = (old >> 58) as u8;
+
+        // Keep the previous best move when re-storing a position without one
+        let best_move = best_move.or_else(|| self.probe(key, ply).and_then(|e| e.best_move));
+
+        let data = pack(depth, bound, score_to_tt(score, ply), best_move, generation);
+
+        let slot =
+            if old == 0 || old_key == key || depth >= old_depth || old_generation != generation {
+                preferred
+            } else {
+                &bucket.slots[1]
+            };
+
+        slot.key.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    // Permille of sampled entries written during the current search
+    pub fn hashfull(&self) -> usize {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sample = self.buckets.len().min(500);
+        let mut used = 0;
+
+        for bucket in &self.buckets[..sample] {
+            for slot in &bucket.slots {
+                let data = slot.data.load(Ordering::Relaxed);
+                if data != 0 && (data >> 58) as u8 == generation {
+                    used += 1;
+                }
+            }
+        }
+
+        used * 1000 / (sample * 2)
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB)
+    }
+}
+
+fn pack(depth: u32, bound: Bound, score: i32, best_move: Option<Move>, generation: u8) -> u64 {
+    let bound_bits: u64 = match bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    let move_bits = best_move.map_or(0, Move::to_bits) as u64;
+
+    (score as u32 as u64)
+        | (move_bits << 32)
+        | ((depth.min(255) as u64) << 48)
+        | (bound_bits << 56)
+        | ((generation as u64 & 0x3F) << 58)
+}
+
+fn unpack(data: u64, ply: usize) -> TtEntry {
+    let move_bits = ((data >> 32) & 0xFFFF) as u16;
+
+    TtEntry {
+        best_move: (move_bits != 0).then(|| Move::from_bits(move_bits)),
+        score: score_from_tt(data as u32 as i32, ply),
+        depth: ((data >> 48) & 0xFF) as u32,
+        bound: match (data >> 56) & 0b11 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            _ => Bound::Upper,
+        },
+    }
+}
+
+// Mate scores are stored relative to the node instead of the root
+#[inline(always)]
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+#[inline(always)]
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::{FLAG_DOUBLE_PUSH, FLAG_QUIET};
+    use crate::search::MATE;
+
+    #[test]
+    fn test_store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let mv = Move::new(12, 28, FLAG_DOUBLE_PUSH);
+        tt.store(0xDEADBEEF, 5, Bound::Exact, -42, 0, Some(mv));
+
+        let entry = tt.probe(0xDEADBEEF, 0).unwrap();
+        assert_eq!(entry.best_move, Some(mv));
+        assert_eq!(entry.score, -42);
+        assert_eq!(entry.depth, 5);
+        assert_eq!(entry.bound, Bound::Exact);
+
+        assert!(tt.probe(0xBEEFDEAD, 0).is_none());
+    }
+
+    #[test]
+    fn test_mate_score_adjusted_by_ply() {
+        let tt = TranspositionTable::new(1);
+
+        // Mate 3 plies below a node at ply 4 scores MATE - 7 from the root
+        tt.store(1, 3, Bound::Exact, MATE - 7, 4, None);
+        assert_eq!(tt.probe(1, 4).unwrap().score, MATE - 7);
+        // The same node reached at ply 2 is mated 5 plies from the root
+        assert_eq!(tt.probe(1, 2).unwrap().score, MATE - 5);
+
+        tt.store(2, 3, Bound::Exact, -MATE + 6, 6, None);
+        assert_eq!(tt.probe(2, 0).unwrap().score, -MATE);
+    }
+
+    #[test]
+    fn test_depth_preferred_replacement() {
+        let tt = TranspositionTable::new(1);
+        let buckets = tt.buckets.len() as u64;
+        // Keys that map to the same bucket
+        let key_a = 1;
+        let key_b = key_a + (u64::MAX / buckets) / 4;
+        let key_c = key_a + (u64::MAX / buckets) / 2;
+
+        tt.store(key_a, 10, Bound::Lower, 1, 0, None);
+        tt.store(key_b, 2, Bound::Lower, 2, 0, None);
+        assert!(tt.probe(key_a, 0).is_some());
+        assert!(tt.probe(key_b, 0).is_some());
+
+        // Shallow entries go to the always-replace slot
+        tt.store(key_c, 1, Bound::Upper, 3, 0, None);
+        assert!(tt.probe(key_a, 0).is_some());
+        assert!(tt.probe(key_b, 0).is_none());
+        assert!(tt.probe(key_c, 0).is_some());
+    }
+
+    #[test]
+    fn test_keeps_best_move_on_overwrite() {
+        let tt = TranspositionTable::new(1);
+        let mv = Move::new(6, 21, FLAG_QUIET);
+        tt.store(7, 3, Bound::Lower, 10, 0, Some(mv));
+        tt.store(7, 4, Bound::Upper, 5, 0, None);
+
+        let entry = tt.probe(7, 0).unwrap();
+        assert_eq!(entry.depth, 4);
+        assert_eq!(entry.best_move, Some(mv));
+    }
+
+    #[test]
+    fn test_hashfull_and_clear() {
+        let tt = TranspositionTable::new(1);
+        assert_eq!(tt.hashfull(), 0);
+
+        for i in 0..tt.buckets.len() as u64 * 2 {
+            tt.store(
+                i.wrapping_mul(0x9E37_79B9_7F4A_7C15),
+                1,
+                Bound::Exact,
+                0,
+                0,
+                None,
+            );
+        }
+        assert!(tt.hashfull() > 300);
+
+        tt.clear();
+        assert_eq!(tt.hashfull(), 0);
+    }
+}
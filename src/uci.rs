@@ -1,17 +1,55 @@
+use std::fs;
 use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 use crate::board::{Board, Color};
-use crate::error::Result;
-use crate::movegen::{generate_moves, Move};
-use crate::eval::evaluate_position;
+use crate::error::{Error, Result};
+use crate::movegen::Move;
+use crate::options::{OptionValue, Options, OPTIONS};
+use crate::perft::{print_divide, PerftOptions};
+use crate::pgn::{Game, GameResult};
+use crate::san::{parse_san, to_san};
+use crate::search::{SearchLimits, SearchResult, Searcher};
+use crate::tt::TranspositionTable;
+
+const INTERACTIVE_DEPTH: u32 = 4;
 
 pub struct UciEngine {
     board: Board,
+    // Keys of the positions before `board` in the current game, oldest first
+    history: Vec<u64>,
+    // Owned by the search thread while a search is running
+    searcher: Option<Searcher>,
+    search_thread: Option<JoinHandle<Searcher>>,
+    stop: Arc<AtomicBool>,
+    // Set during `go ponder` until `ponderhit` or `stop`
+    pondering: Arc<AtomicBool>,
+    // Wakes a finished search that waits for `stop` or `ponderhit` to report
+    release: Arc<(Mutex<()>, Condvar)>,
+    options: Options,
+    // Options set during a search, the searcher only gets them afterwards
+    pending_options: Vec<(&'static str, OptionValue)>,
 }
 
 impl UciEngine {
     pub fn new() -> Self {
+        let mut searcher = Searcher::new();
+        searcher.set_print_info(true);
+        let stop = searcher.stop_flag();
+        let pondering = searcher.ponder_flag();
+
         Self {
             board: Board::default(),
+            history: Vec::new(),
+            searcher: Some(searcher),
+            search_thread: None,
+            stop,
+            pondering,
+            release: Arc::new((Mutex::new(()), Condvar::new())),
+            options: Options::new(),
+            pending_options: Vec::new(),
         }
     }
 
@@ -24,6 +62,9 @@ impl UciEngine {
                 Ok(0) => break,
                 Ok(_) => {
                     let command = input.trim();
+                    if command == "quit" {
+                        break;
+                    }
                     if let Err(e) = self.handle_uci_command(command) {
                         eprintln!("Error: {}", e);
                     }
@@ -35,6 +76,8 @@ impl UciEngine {
             }
         }
 
+        self.stop_search();
+
         Ok(())
     }
 
@@ -48,11 +91,16 @@ impl UciEngine {
         match parts[0] {
             "uci" => self.uci_identify(),
             "isready" => self.is_ready(),
+            "setoption" => self.uci_setoption(&parts[1..])?,
             "ucinewgame" => self.uci_new_game(),
             "position" => self.uci_position(&parts[1..])?,
-            "go" => self.uci_go()?,
-            "stop" => {}
-            "quit" => std::process::exit(0),
+            "go" => self.uci_go(&parts[1..])?,
+            "stop" => self.stop_search(),
+            "ponderhit" => {
+                self.pondering.store(false, Ordering::Relaxed);
+                self.release_search();
+            }
+            "d" => self.print_board(),
             _ => {}
         }
 
@@ -62,15 +110,77 @@ impl UciEngine {
     fn uci_identify(&self) {
         println!("id name BrainyBishop 0.1.0");
         println!("id author BrainyBishop Team");
+        for option in OPTIONS {
+            println!("{}", option);
+        }
         println!("uciok");
     }
 
+    fn uci_setoption(&mut self, args: &[&str]) -> Result<()> {
+        let (name, value) = parse_setoption(args)?;
+        let (option, value) = self.options.set(&name, value.as_deref())?;
+
+        // A running search keeps going with the old settings
+        match self.searcher.as_mut() {
+            Some(searcher) => apply_option(searcher, option.name, value),
+            None => self.pending_options.push((option.name, value)),
+        }
+
+        Ok(())
+    }
+
+    // Non-standard debugging command, as in most engines
+    fn print_board(&self) {
+        self.board.display();
+        println!("Fen: {}", self.board.to_fen());
+        println!("Key: {:016X}", self.board.hash);
+    }
+
+    // Answered straight away, even while searching
     fn is_ready(&self) {
         println!("readyok");
     }
 
     fn uci_new_game(&mut self) {
         self.board = Board::default();
+        self.history.clear();
+        self.searcher_mut().tt().clear();
+        self.searcher_mut().clear_history_tables();
+    }
+
+    // Waits for a running search to finish and takes the searcher back
+    fn wait_for_search(&mut self) {
+        if let Some(handle) = self.search_thread.take() {
+            let mut searcher = handle.join().expect("search thread panicked");
+            for (name, value) in self.pending_options.drain(..) {
+                apply_option(&mut searcher, name, value);
+            }
+            self.searcher = Some(searcher);
+        }
+    }
+
+    // Stops a running search first, one waiting for `stop` would otherwise
+    // never finish
+    fn searcher_mut(&mut self) -> &mut Searcher {
+        self.stop_search();
+        self.searcher.as_mut().expect("searcher is owned by the engine")
+    }
+
+    fn stop_search(&mut self) {
+        if self.search_thread.is_some() {
+            self.stop.store(true, Ordering::Relaxed);
+            self.pondering.store(false, Ordering::Relaxed);
+            self.release_search();
+            self.wait_for_search();
+        }
+    }
+
+    // The lock is taken so the wakeup can't slip in between the search
+    // thread checking the flags and starting to wait
+    fn release_search(&self) {
+        let (lock, condvar) = &*self.release;
+        let _guard = lock.lock().expect("release lock poisoned");
+        condvar.notify_all();
     }
 
     fn uci_position(&mut self, args: &[&str]) -> Result<()> {
@@ -78,211 +188,235 @@ impl UciEngine {
             return Ok(());
         }
 
-        match args[0] {
+        self.stop_search();
+
+        let (mut board, moves) = match args[0] {
             "startpos" => {
-                self.board = Board::default();
-                if args.len() > 1 && args[1] == "moves" {
-                    for move_str in &args[2..] {
-                        self.apply_move(move_str)?;
-                    }
-                }
+                let moves = match args.get(1) {
+                    Some(&"moves") => &args[2..],
+                    _ => &[],
+                };
+                (Board::default(), moves)
             }
             "fen" => {
-                let mut fen_parts = Vec::new();
-                let mut i = 1;
-                while i < args.len() && args[i] != "moves" {
-                    fen_parts.push(args[i]);
-                    i += 1;
-                }
-                let fen_string = fen_parts.join(" ");
-                self.board = Board::from_fen(&fen_string)?;
-
-                if i < args.len() && args[i] == "moves" {
-                    for move_str in &args[i + 1..] {
-                        self.apply_move(move_str)?;
-                    }
-                }
+                let end = args.iter().position(|&arg| arg == "moves").unwrap_or(args.len());
+                let fen_string = args[1..end].join(" ");
+                // Some GUIs leave out the move counters
+                let board = Board::from_fen_lenient(&fen_string)?;
+                (board, args.get(end + 1..).unwrap_or_default())
             }
-            _ => {}
+            _ => return Ok(()),
+        };
+
+        // The position only changes once every move has been played
+        let mut history = Vec::with_capacity(moves.len());
+        for move_str in moves {
+            let mv = Move::from_uci(&board, move_str)?;
+            history.push(board.hash);
+            board = board.make_move(mv);
         }
+        self.board = board;
+        self.history = history;
 
         Ok(())
     }
 
-    fn uci_go(&self) -> Result<()> {
-        let best_move = find_best_move(&self.board, 3);
-
-        match best_move {
-            Some(mv) => println!("bestmove {}", mv),
-            None => println!("bestmove 0000"),
+    fn uci_go(&mut self, args: &[&str]) -> Result<()> {
+        self.stop_search();
+
+        // go perft <depth> runs to completion before the next command is read
+        if let ["perft", depth] = args {
+            let depth = depth.parse().map_err(Error::ParseError)?;
+            let options = PerftOptions {
+                hash_mb: self.options.spin("Hash") as usize,
+                threads: self.options.spin("Threads") as usize,
+                chess960: self.options.check("UCI_Chess960"),
+            };
+            print_divide(&self.board, depth, options);
+            return Ok(());
         }
 
-        Ok(())
-    }
-
-    fn apply_move(&mut self, move_str: &str) -> Result<()> {
-        let moves = generate_moves(&self.board);
-
-        for mv in moves.iter() {
-            if mv.to_string() == move_str {
-                self.board = self.board.make_move(*mv);
-                return Ok(());
+        let limits = parse_go(&self.board, args)?;
+        let mut searcher = self.searcher.take().expect("searcher is owned by the engine");
+        searcher.set_history(&self.history);
+
+        self.stop.store(false, Ordering::Relaxed);
+        self.pondering.store(limits.ponder, Ordering::Relaxed);
+
+        let board = self.board;
+        let chess960 = self.options.check("UCI_Chess960");
+        let stop = Arc::clone(&self.stop);
+        let pondering = Arc::clone(&self.pondering);
+        let release = Arc::clone(&self.release);
+
+        self.search_thread = Some(thread::spawn(move || {
+            let result = searcher.search(&board, &limits);
+
+            // Infinite and ponder searches must not report until told to
+            let (lock, condvar) = &*release;
+            let mut guard = lock.lock().expect("release lock poisoned");
+            while (limits.infinite || pondering.load(Ordering::Relaxed))
+                && !stop.load(Ordering::Relaxed)
+            {
+                guard = condvar.wait(guard).expect("release lock poisoned");
             }
-        }
+            drop(guard);
 
-        Err(crate::error::Error::InvalidMove(move_str.to_string()))
-    }
-}
+            report_result(&result, chess960);
+            searcher
+        }));
 
-impl Default for UciEngine {
-    fn default() -> Self {
-        Self::new()
+        Ok(())
     }
 }
 
-fn find_best_move(board: &Board, depth: u32) -> Option<Move> {
-    let moves = generate_moves(board);
-
-    if moves.is_empty() {
-        return None;
-    }
-
-    let maximizing = board.side_to_move() == Color::White;
-    let mut best_move = None;
-    let mut best_score = if maximizing { i32::MIN } else { i32::MAX };
-
-    for mv in moves.iter() {
-        let new_board = board.make_move(*mv);
-        let score = minimax(&new_board, depth - 1, i32::MIN, i32::MAX, !maximizing);
-
-        if maximizing {
-            if score > best_score {
-                best_score = score;
-                best_move = Some(*mv);
-            }
-        } else if score < best_score {
-            best_score = score;
-            best_move = Some(*mv);
+// Ponder only needs to be remembered in the registry
+fn apply_option(searcher: &mut Searcher, name: &str, value: OptionValue) {
+    match (name, value) {
+        ("Hash", OptionValue::Spin(mb)) => {
+            searcher.set_tt(Arc::new(TranspositionTable::new(mb as usize)));
+        }
+        ("Threads", OptionValue::Spin(threads)) => searcher.set_threads(threads as usize),
+        ("Clear Hash", _) => searcher.tt().clear(),
+        ("MultiPV", OptionValue::Spin(lines)) => searcher.set_multi_pv(lines as usize),
+        ("Move Overhead", OptionValue::Spin(ms)) => {
+            searcher.set_move_overhead(Duration::from_millis(ms as u64));
         }
+        ("UCI_Chess960", OptionValue::Check(chess960)) => searcher.set_chess960(chess960),
+        _ => {}
     }
-
-    best_move
 }
 
-fn minimax(board: &Board, depth: u32, mut alpha: i32, mut beta: i32, maximizing: bool) -> i32 {
-    if depth == 0 {
-        return evaluate_position(board);
+// setoption name <id> [value <x>], both may contain spaces
+fn parse_setoption(args: &[&str]) -> Result<(String, Option<String>)> {
+    if args.first() != Some(&"name") {
+        return Err(Error::InvalidCommand("setoption: expected name".to_string()));
     }
 
-    let moves = generate_moves(board);
-
-    if moves.is_empty() {
-        // Check if it's checkmate or stalemate
-        let king_sq = board.king_square(board.side_to_move());
-        let in_check = is_square_attacked(board, king_sq, board.side_to_move().opposite());
+    let value_idx = args.iter().position(|&arg| arg == "value");
+    let name = args[1..value_idx.unwrap_or(args.len())].join(" ");
+    let value = value_idx.map(|idx| args[idx + 1..].join(" "));
 
-        if in_check {
-            // Checkmate
-            return if maximizing { -100000 + depth as i32 } else { 100000 - depth as i32 };
-        } else {
-            // Stalemate
-            return 0;
-        }
+    if name.is_empty() {
+        return Err(Error::InvalidCommand("setoption: missing name".to_string()));
     }
 
-    if maximizing {
-        let mut max_eval = i32::MIN;
-        for mv in moves.iter() {
-            let new_board = board.make_move(*mv);
-            let eval = minimax(&new_board, depth - 1, alpha, beta, false);
-            max_eval = max_eval.max(eval);
-            alpha = alpha.max(eval);
-            if beta <= alpha {
-                break;
+    Ok((name, value))
+}
+
+fn parse_go(board: &Board, args: &[&str]) -> Result<SearchLimits> {
+    let mut limits = SearchLimits::default();
+    let mut i = 0;
+
+    // Some GUIs send negative clock times when flagging
+    let millis = |value: Option<&&str>| -> Result<Duration> {
+        let value = value.ok_or_else(|| Error::InvalidCommand("go: missing value".to_string()))?;
+        let ms: i64 = value.parse().map_err(Error::ParseError)?;
+        Ok(Duration::from_millis(ms.max(0) as u64))
+    };
+    let number = |value: Option<&&str>| -> Result<u64> {
+        let value = value.ok_or_else(|| Error::InvalidCommand("go: missing value".to_string()))?;
+        value.parse().map_err(Error::ParseError)
+    };
+    let count = |value: Option<&&str>| -> Result<u32> {
+        let value = value.ok_or_else(|| Error::InvalidCommand("go: missing value".to_string()))?;
+        value
+            .parse()
+            .map_err(|_| Error::InvalidCommand(format!("go: invalid count '{}'", value)))
+    };
+
+    while i < args.len() {
+        let value = args.get(i + 1);
+        match args[i] {
+            "wtime" => limits.wtime = Some(millis(value)?),
+            "btime" => limits.btime = Some(millis(value)?),
+            "winc" => limits.winc = Some(millis(value)?),
+            "binc" => limits.binc = Some(millis(value)?),
+            "movetime" => limits.movetime = Some(millis(value)?),
+            "movestogo" => limits.movestogo = Some(count(value)?),
+            "depth" => limits.depth = Some(count(value)?),
+            "nodes" => limits.nodes = Some(number(value)?),
+            "mate" => limits.mate = Some(count(value)?),
+            "infinite" => {
+                limits.infinite = true;
+                i += 1;
+                continue;
             }
-        }
-        max_eval
-    } else {
-        let mut min_eval = i32::MAX;
-        for mv in moves.iter() {
-            let new_board = board.make_move(*mv);
-            let eval = minimax(&new_board, depth - 1, alpha, beta, true);
-            min_eval = min_eval.min(eval);
-            beta = beta.min(eval);
-            if beta <= alpha {
-                break;
+            "ponder" => {
+                limits.ponder = true;
+                i += 1;
+                continue;
+            }
+            "searchmoves" => {
+                i += 1;
+                while let Some(mv) = args.get(i).and_then(|s| Move::from_uci(board, s).ok()) {
+                    limits.searchmoves.push(mv);
+                    i += 1;
+                }
+                continue;
+            }
+            _ => {
+                i += 1;
+                continue;
             }
         }
-        min_eval
+        i += 2;
     }
-}
-
-fn is_square_attacked(board: &Board, sq: usize, by_color: Color) -> bool {
-    use crate::magic::{bishop_attacks, rook_attacks};
-    use crate::tables::{KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS};
-    use crate::board::PieceType;
-
-    let occ = board.all_occupancy();
 
-    // Pawn attacks
-    let pawn_attacks = PAWN_ATTACKS[by_color.opposite().index()][sq];
-    if pawn_attacks & board.pieces(PieceType::Pawn, by_color) != 0 {
-        return true;
-    }
-
-    // Knight attacks
-    if KNIGHT_ATTACKS[sq] & board.pieces(PieceType::Knight, by_color) != 0 {
-        return true;
-    }
-
-    // Bishop/Queen attacks
-    let bishop_rays = bishop_attacks(sq, occ);
-    if bishop_rays & (board.pieces(PieceType::Bishop, by_color) | board.pieces(PieceType::Queen, by_color)) != 0 {
-        return true;
-    }
+    Ok(limits)
+}
 
-    // Rook/Queen attacks
-    let rook_rays = rook_attacks(sq, occ);
-    if rook_rays & (board.pieces(PieceType::Rook, by_color) | board.pieces(PieceType::Queen, by_color)) != 0 {
-        return true;
+// The info lines have already been printed by the searcher
+fn report_result(result: &SearchResult, chess960: bool) {
+    match (result.best_move, result.pv.get(1)) {
+        (Some(mv), Some(ponder)) => println!(
+            "bestmove {} ponder {}",
+            mv.to_uci_with(chess960),
+            ponder.to_uci_with(chess960)
+        ),
+        (Some(mv), None) => println!("bestmove {}", mv.to_uci_with(chess960)),
+        (None, _) => println!("bestmove 0000"),
     }
+}
 
-    // King attacks
-    if KING_ATTACKS[sq] & board.pieces(PieceType::King, by_color) != 0 {
-        return true;
+impl Default for UciEngine {
+    fn default() -> Self {
+        Self::new()
     }
-
-    false
 }
 
 pub fn run_interactive_mode(player_color: Color) -> Result<()> {
     println!("brainybishop - {}", env!("CARGO_PKG_VERSION"));
 
     let computer_color = player_color.opposite();
-    let mut board = Board::default();
+    let mut game = Game::new();
+    let mut searcher = Searcher::new();
     let mut input = String::new();
 
+    let engine_name = format!("brainybishop {}", env!("CARGO_PKG_VERSION"));
+    let (white, black) = match player_color {
+        Color::White => ("Player", engine_name.as_str()),
+        Color::Black => (engine_name.as_str(), "Player"),
+    };
+    game.set_tag("Event", "Interactive game");
+    game.set_tag("White", white);
+    game.set_tag("Black", black);
+
     loop {
-        board.display();
+        game.board().display();
         println!();
 
-        let moves = generate_moves(&board);
-        if moves.is_empty() {
-            let king_sq = board.king_square(board.side_to_move());
-            let in_check = is_square_attacked(&board, king_sq, board.side_to_move().opposite());
-
-            if in_check {
-                println!("checkmate");
-            } else {
-                println!("stalemate");
-            }
-            break;
-        }
-
-        if board.side_to_move() == computer_color {
-            if let Some(mv) = find_best_move(&board, 4) {
-                println!("{}", mv);
-                board = board.make_move(mv);
+        // Once the game is over it can still be saved before quitting
+        let status = game.status();
+        if status.is_game_over() {
+            game.result = GameResult::from_status(status, game.board().side_to_move());
+            println!("{} {}", status, game.result);
+        } else if game.board().side_to_move() == computer_color {
+            searcher.set_history(&game.hashes());
+            let limits = SearchLimits::depth(INTERACTIVE_DEPTH);
+            if let Some(mv) = searcher.search(game.board(), &limits).best_move {
+                println!("{}", to_san(game.board(), mv));
+                game.push(mv);
             } else {
                 break;
             }
@@ -303,27 +437,44 @@ pub fn run_interactive_mode(player_color: Color) -> Result<()> {
                 }
 
                 if command == "help" || command == "?" {
-                    println!("moves: e2e4, g1f3, e7e8q");
+                    println!("moves: e4, Nf3, exd5, O-O, e8=Q or e2e4, g1f3, e7e8q");
+                    println!("fen: print the position as FEN");
+                    println!("save <file>: write the game so far as PGN");
                     println!("quit, q: exit");
                     continue;
                 }
 
-                if command.is_empty() {
+                if command == "fen" {
+                    println!("{}", game.board().to_fen());
                     continue;
                 }
 
-                // Try to parse as a move
-                let mut found = false;
-                for mv in moves.iter() {
-                    if mv.to_string() == command {
-                        board = board.make_move(*mv);
-                        found = true;
-                        break;
+                if command == "save" || command.starts_with("save ") {
+                    match command["save".len()..].trim() {
+                        "" => println!("usage: save <file>"),
+                        path => match fs::write(path, game.to_string()) {
+                            Ok(()) => println!("saved to {}", path),
+                            Err(e) => println!("could not save to {}: {}", path, e),
+                        },
                     }
+                    continue;
+                }
+
+                if status.is_game_over() {
+                    println!("the game is over, save it or quit");
+                    continue;
                 }
 
-                if !found {
-                    println!("invalid: {}", command);
+                if command.is_empty() {
+                    continue;
+                }
+
+                // Coordinate notation first, anything else is read as SAN
+                let board = game.board();
+                let mv = Move::from_uci(board, command).or_else(|_| parse_san(board, command));
+                match mv {
+                    Ok(mv) => game.push(mv),
+                    Err(e) => println!("{}", e),
                 }
             }
             Err(e) => {
@@ -336,3 +487,143 @@ pub fn run_interactive_mode(player_color: Color) -> Result<()> {
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::MoveError;
+
+    #[test]
+    fn test_parse_go_clock() {
+        let board = Board::default();
+        let args: Vec<&str> = "wtime 60000 btime -20 winc 1000 binc 1000 movestogo 30"
+            .split_whitespace()
+            .collect();
+        let limits = parse_go(&board, &args).unwrap();
+
+        assert_eq!(limits.wtime, Some(Duration::from_millis(60000)));
+        assert_eq!(limits.btime, Some(Duration::ZERO));
+        assert_eq!(limits.winc, Some(Duration::from_millis(1000)));
+        assert_eq!(limits.binc, Some(Duration::from_millis(1000)));
+        assert_eq!(limits.movestogo, Some(30));
+        assert!(!limits.infinite);
+    }
+
+    #[test]
+    fn test_parse_go_limits() {
+        let board = Board::default();
+        let args: Vec<&str> = "ponder depth 7 nodes 100000 movetime 2500 mate 3 infinite"
+            .split_whitespace()
+            .collect();
+        let limits = parse_go(&board, &args).unwrap();
+
+        assert!(limits.ponder);
+        assert!(limits.infinite);
+        assert_eq!(limits.depth, Some(7));
+        assert_eq!(limits.nodes, Some(100000));
+        assert_eq!(limits.movetime, Some(Duration::from_millis(2500)));
+        assert_eq!(limits.mate, Some(3));
+
+        // Counts beyond u32 are rejected rather than wrapped
+        for args in [["depth", "4294967296"], ["movestogo", "-1"], ["mate", "x"]] {
+            assert!(matches!(
+                parse_go(&board, &args),
+                Err(Error::InvalidCommand(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_go_searchmoves() {
+        let board = Board::default();
+        let args: Vec<&str> = "searchmoves e2e4 d2d4 depth 5"
+            .split_whitespace()
+            .collect();
+        let limits = parse_go(&board, &args).unwrap();
+
+        let moves: Vec<String> = limits.searchmoves.iter().map(|mv| mv.to_uci()).collect();
+        assert_eq!(moves, vec!["e2e4", "d2d4"]);
+        assert_eq!(limits.depth, Some(5));
+    }
+
+    #[test]
+    fn test_parse_setoption() {
+        let args: Vec<&str> = "name Move Overhead value 30".split_whitespace().collect();
+        assert_eq!(
+            parse_setoption(&args).unwrap(),
+            ("Move Overhead".to_string(), Some("30".to_string()))
+        );
+
+        let args: Vec<&str> = "name Clear Hash".split_whitespace().collect();
+        assert_eq!(parse_setoption(&args).unwrap(), ("Clear Hash".to_string(), None));
+
+        assert!(parse_setoption(&["value", "3"]).is_err());
+        assert!(parse_setoption(&["name"]).is_err());
+    }
+
+    #[test]
+    fn test_setoption_updates_engine() {
+        let mut engine = UciEngine::new();
+        engine
+            .handle_uci_command("setoption name Hash value 1")
+            .unwrap();
+        engine
+            .handle_uci_command("setoption name UCI_Chess960 value true")
+            .unwrap();
+
+        assert_eq!(engine.options.spin("Hash"), 1);
+        assert!(engine.options.check("UCI_Chess960"));
+        assert!(engine
+            .handle_uci_command("setoption name Threads value 0")
+            .is_err());
+        assert!(engine
+            .handle_uci_command("setoption name Nonsense value 1")
+            .is_err());
+    }
+
+    #[test]
+    fn test_setoption_during_search() {
+        let mut engine = UciEngine::new();
+        engine.handle_uci_command("go infinite").unwrap();
+        engine
+            .handle_uci_command("setoption name MultiPV value 3")
+            .unwrap();
+
+        assert!(engine.search_thread.is_some());
+        assert_eq!(engine.options.spin("MultiPV"), 3);
+        assert_eq!(engine.pending_options.len(), 1);
+
+        engine.handle_uci_command("stop").unwrap();
+        assert!(engine.search_thread.is_none());
+        assert!(engine.pending_options.is_empty());
+    }
+
+    #[test]
+    fn test_position_rejects_illegal_move() {
+        let mut engine = UciEngine::new();
+        engine
+            .handle_uci_command("position startpos moves e2e4 e7e5 g1f3")
+            .unwrap();
+        assert_eq!(engine.history.len(), 3);
+
+        let result = engine.handle_uci_command("position startpos moves e2e4 e2e4");
+        assert!(matches!(result, Err(Error::InvalidMove(_, MoveError::NoPiece))));
+
+        // The previous position is kept rather than half of the new one
+        assert_eq!(engine.history.len(), 3);
+        assert_eq!(
+            engine.board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+    }
+
+    #[test]
+    fn test_go_perft() {
+        let mut engine = UciEngine::new();
+        engine.handle_uci_command("go perft 2").unwrap();
+        assert!(engine.search_thread.is_none());
+        assert!(matches!(
+            engine.handle_uci_command("go perft deep"),
+            Err(Error::ParseError(_))
+        ));
+    }
+}
@@ -0,0 +1,68 @@
+// Zobrist keys for position hashing, generated at compile time
+
+pub static PIECE_KEYS: [[u64; 64]; 12] = generate_piece_keys();
+pub static CASTLING_KEYS: [u64; 16] = generate_keys::<16>(0x5EED_0002);
+pub static EN_PASSANT_KEYS: [u64; 8] = generate_keys::<8>(0x5EED_0003);
+pub const SIDE_KEY: u64 = splitmix64(0x5EED_0004).1;
+
+// SplitMix64, returns (next state, output)
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0u64; N];
+    let mut state = seed;
+    let mut i = 0;
+
+    while i < N {
+        let (next, key) = splitmix64(state);
+        state = next;
+        keys[i] = key;
+        i += 1;
+    }
+
+    keys
+}
+
+const fn generate_piece_keys() -> [[u64; 64]; 12] {
+    let mut keys = [[0u64; 64]; 12];
+    let mut state = 0x5EED_0001u64;
+    let mut piece = 0;
+
+    while piece < 12 {
+        let mut sq = 0;
+        while sq < 64 {
+            let (next, key) = splitmix64(state);
+            state = next;
+            keys[piece][sq] = key;
+            sq += 1;
+        }
+        piece += 1;
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_unique() {
+        let mut all: Vec<u64> = PIECE_KEYS.iter().flatten().copied().collect();
+        all.extend_from_slice(&CASTLING_KEYS);
+        all.extend_from_slice(&EN_PASSANT_KEYS);
+        all.push(SIDE_KEY);
+
+        let count = all.len();
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), count);
+        assert!(!all.contains(&0));
+    }
+}
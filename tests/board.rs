@@ -3,6 +3,7 @@ use brainybishop::board::{Board, CastlingRights, Color};
 #[cfg(test)]
 mod tests {
     use brainybishop::board::{Piece, PieceType, Square};
+    use brainybishop::movegen::generate_moves;
 
     use super::*;
 
@@ -132,4 +133,340 @@ mod tests {
         assert_eq!(board.occupancy(Color::White), 0xFFFF);
         assert_eq!(board.occupancy(Color::Black), 0xFFFF000000000000);
     }
+
+    #[test]
+    fn test_hash_matches_from_fen() {
+        let board = Board::default();
+        let from_fen =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(board.hash, from_fen.hash);
+        assert_eq!(board.hash, board.compute_hash());
+    }
+
+    #[test]
+    fn test_hash_incremental_update() {
+        let mut board = Board::default();
+        for uci in ["e2e4", "c7c5", "g1f3", "d7d6", "f1b5", "c8d7"] {
+            let mv = generate_moves(&board)
+                .iter()
+                .copied()
+                .find(|mv| mv.to_uci() == uci)
+                .unwrap();
+            board = board.make_move(mv);
+            assert_eq!(board.hash, board.compute_hash());
+        }
+
+        let expected = Board::from_fen(
+            "rn1qkbnr/pp1bpppp/3p4/1Bp5/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 2 4",
+        )
+        .unwrap();
+        assert_eq!(board.hash, expected.hash);
+    }
+
+    #[test]
+    fn test_hash_transposition() {
+        let play = |moves: &[&str]| {
+            let mut board = Board::default();
+            for uci in moves {
+                let mv = generate_moves(&board)
+                    .iter()
+                    .copied()
+                    .find(|mv| mv.to_uci() == *uci)
+                    .unwrap();
+                board = board.make_move(mv);
+            }
+            board
+        };
+
+        let a = play(&["g1f3", "g8f6", "b1c3"]);
+        let b = play(&["b1c3", "g8f6", "g1f3"]);
+        assert_eq!(a.hash, b.hash);
+
+        // Knights returning home reach the starting position again
+        let c = play(&["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(c.hash, Board::default().hash);
+    }
+
+    #[test]
+    fn test_hash_side_castling_and_en_passant() {
+        let white = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let black = Board::from_fen("4k3/8/8/8/8/8/8/4K2R b K - 0 1").unwrap();
+        let no_castle = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+        assert_ne!(white.hash, black.hash);
+        assert_ne!(white.hash, no_castle.hash);
+
+        let ep = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let no_ep = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_ne!(ep.hash, no_ep.hash);
+    }
+
+    #[test]
+    fn test_repetitions() {
+        let mut board = Board::default();
+        let mut history = Vec::new();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+        for (i, uci) in shuffle.iter().chain(shuffle.iter()).enumerate() {
+            let mv = generate_moves(&board)
+                .iter()
+                .copied()
+                .find(|mv| mv.to_uci() == *uci)
+                .unwrap();
+            history.push(board.hash);
+            board = board.make_move(mv);
+
+            if i == 3 {
+                assert_eq!(board.repetitions(&history), 1);
+            }
+        }
+        assert_eq!(board.repetitions(&history), 2);
+
+        // A pawn move makes the earlier positions unreachable
+        let mut reset = board;
+        reset.halfmove = 0;
+        assert_eq!(reset.repetitions(&history), 0);
+    }
+
+    #[test]
+    fn test_fifty_move_draw() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 99 80").unwrap();
+        assert!(!board.is_fifty_move_draw());
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 100 80").unwrap();
+        assert!(board.is_fifty_move_draw());
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let drawn = [
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3KB3/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/6n1/8 b - - 0 1",
+            // Bishops on c1 and f8 are both on dark squares
+            "5b2/8/4k3/8/8/3K4/8/2B5 w - - 0 1",
+        ];
+        for fen in drawn {
+            let board = Board::from_fen(fen).unwrap();
+            assert!(board.is_insufficient_material(), "{}", fen);
+        }
+
+        let playable = [
+            // Opposite coloured bishops can still mate with help
+            "2b5/8/4k3/8/8/3K4/8/2B5 w - - 0 1",
+            "8/8/4k3/8/8/3KN3/6n1/8 w - - 0 1",
+            "8/8/4k3/8/8/3KBN2/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/7P/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/7R/8 w - - 0 1",
+        ];
+        for fen in playable {
+            let board = Board::from_fen(fen).unwrap();
+            assert!(!board.is_insufficient_material(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_game_status() {
+        use brainybishop::board::GameStatus;
+
+        let status = |fen: &str| Board::from_fen(fen).unwrap().status(&[]);
+
+        assert_eq!(Board::default().status(&[]), GameStatus::Ongoing);
+        assert_eq!(status("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1"), GameStatus::Checkmate);
+        assert_eq!(status("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), GameStatus::Stalemate);
+        assert_eq!(status("8/8/4k3/8/8/3K4/8/8 w - - 0 1"), GameStatus::InsufficientMaterial);
+        assert_eq!(status("4k3/8/8/8/8/8/8/4K2R w K - 100 80"), GameStatus::FiftyMoveRule);
+        // Mate on the hundredth half-move still counts
+        assert_eq!(status("R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80"), GameStatus::Checkmate);
+
+        assert!(GameStatus::Stalemate.is_draw());
+        assert!(!GameStatus::Checkmate.is_draw());
+        assert!(GameStatus::Checkmate.is_game_over());
+    }
+
+    #[test]
+    fn test_make_unmake_restores_position() {
+        fn walk(board: &mut Board, depth: u32) {
+            if depth == 0 {
+                return;
+            }
+            for mv in generate_moves(board).iter() {
+                let before = *board;
+                let undo = board.make_move_mut(*mv);
+                assert_eq!(*board, before.make_move(*mv), "make {}", mv);
+
+                walk(board, depth - 1);
+
+                board.unmake_move(*mv, undo);
+                assert_eq!(*board, before, "unmake {}", mv);
+            }
+        }
+
+        let fens = [
+            // Castling both ways, en passant and promotions with capture
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkb1r/ppp1pppp/5n2/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ];
+        for fen in fens {
+            walk(&mut Board::from_fen(fen).unwrap(), 2);
+        }
+    }
+
+    #[test]
+    fn test_to_fen() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(Board::default().to_fen(), start);
+        assert_eq!(Board::default().to_string(), start);
+
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq - 3 17";
+        assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+
+        let parsed: Board = fen.parse().unwrap();
+        assert_eq!(parsed, Board::from_fen(fen).unwrap());
+    }
+
+    #[test]
+    fn test_to_fen_en_passant() {
+        use brainybishop::board::FenOptions;
+
+        // After 1. e4 no black pawn can take on e3
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+        let options = FenOptions {
+            always_en_passant: true,
+            ..FenOptions::default()
+        };
+        assert_eq!(board.to_fen_with(options), fen);
+
+        // exd6 is possible
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+        assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+
+        // The e5 pawn is pinned against the king and can't capture
+        let fen = "4k3/8/8/K2pP2r/8/8/8/8 w - d6 0 1";
+        assert_eq!(
+            Board::from_fen(fen).unwrap().to_fen(),
+            "4k3/8/8/K2pP2r/8/8/8/8 w - - 0 1"
+        );
+    }
+
+    #[test]
+    fn test_chess960_castling_fen() {
+        use brainybishop::board::FenOptions;
+
+        let shredder = FenOptions {
+            shredder: true,
+            ..FenOptions::default()
+        };
+
+        // Shredder-FEN in, X-FEN out when every castling rook is the outermost
+        let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 0 9";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.castling_rooks, [7, 5, 63, 61]);
+        assert_eq!(
+            board.to_fen(),
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 0 9"
+        );
+        assert_eq!(board.to_fen_with(shredder), fen);
+        assert_eq!(Board::from_fen(&board.to_fen()).unwrap(), board);
+
+        // An inner rook is named by its file, KQkq pick the outermost rook
+        let board = Board::from_fen("rk2r2r/8/8/8/8/8/8/RK2R2R w Eq - 0 1").unwrap();
+        assert_eq!(board.castling_rooks[0], 4);
+        assert_eq!(board.castling_rooks[3], 56);
+        assert_eq!(board.to_fen(), "rk2r2r/8/8/8/8/8/8/RK2R2R w Eq - 0 1");
+        assert_eq!(board.to_fen_with(shredder), "rk2r2r/8/8/8/8/8/8/RK2R2R w Ea - 0 1");
+        let board = Board::from_fen("rk2r2r/8/8/8/8/8/8/RK2R2R w K - 0 1").unwrap();
+        assert_eq!(board.castling_rooks[0], 7);
+    }
+
+    #[test]
+    fn test_from_fen_rejects_invalid() {
+        use brainybishop::error::{Error, FenError};
+
+        let cases = [
+            ("8/8/8/8/8/8/8/4K2k w - -", FenError::FieldCount(4)),
+            ("4k3/8/8/8/8/8/4K3 w - - 0 1", FenError::RankCount(7)),
+            (
+                "4k3/8/8/8/8/8/8/4K4 w - - 0 1",
+                FenError::RankLength {
+                    rank: 1,
+                    squares: 9,
+                },
+            ),
+            (
+                "4k3/8/8/8/3x4/8/8/4K3 w - - 0 1",
+                FenError::InvalidPiece {
+                    rank: 4,
+                    piece: 'x',
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8/4K3 w - - 0 1",
+                FenError::KingCount {
+                    color: Color::Black,
+                    count: 0,
+                },
+            ),
+            (
+                "4k3/8/8/8/8/8/8/P3K3 w - - 0 1",
+                FenError::PawnOnBackRank("a1".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 x - - 0 1",
+                FenError::InvalidSideToMove("x".to_string()),
+            ),
+            ("4k3/8/8/8/8/8/8/4K2R w KX - 0 1", FenError::InvalidCastling('X')),
+            ("4k3/8/8/8/8/8/8/4K2R w KK - 0 1", FenError::InvalidCastling('K')),
+            ("4k3/8/8/8/8/8/8/4K2R w Q - 0 1", FenError::CastlingWithoutPieces('Q')),
+            ("4k3/8/8/8/8/8/8/4K2R w G - 0 1", FenError::CastlingWithoutPieces('G')),
+            ("4k3/8/8/8/8/8/8/4K2R w E - 0 1", FenError::CastlingWithoutPieces('E')),
+            ("4k3/8/8/8/8/8/4K3/7R w K - 0 1", FenError::CastlingWithoutPieces('K')),
+            ("4k3/8/8/8/8/8/8/4K2R w HK - 0 1", FenError::InvalidCastling('K')),
+            (
+                "4k3/8/8/3pP3/8/8/8/4K3 w - d3 0 1",
+                FenError::InvalidEnPassant("d3".to_string()),
+            ),
+            (
+                "4k3/8/8/4P3/8/8/8/4K3 w - d6 0 1",
+                FenError::ImpossibleEnPassant("d6".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - x 1",
+                FenError::InvalidHalfmove("x".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 -1",
+                FenError::InvalidFullmove("-1".to_string()),
+            ),
+            ("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", FenError::OpponentInCheck),
+        ];
+
+        for (fen, expected) in cases {
+            match Board::from_fen(fen) {
+                Err(Error::InvalidFen(error)) => assert_eq!(error, expected, "{}", fen),
+                other => panic!("{} parsed as {:?}", fen, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_fen_lenient() {
+        let epd = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3";
+        assert!(Board::from_fen(epd).is_err());
+
+        let board = Board::from_fen_lenient(epd).unwrap();
+        assert_eq!(board.halfmove, 0);
+        assert_eq!(board.fullmove, 1);
+        assert_eq!(board.en_passant, Some(Square::from_algebraic("e3").unwrap()));
+
+        // Only the counters are optional, the rest is still validated
+        assert!(Board::from_fen_lenient("4k3/8/8/8/8/8/8/4K3 w -").is_err());
+        assert!(Board::from_fen_lenient("4k3/8/8/8/8/8/8/4K3 w Q -").is_err());
+    }
 }
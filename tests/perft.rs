@@ -1,23 +1,65 @@
-use brainybishop::board::Board;
+use std::thread;
+
+use brainybishop::board::{Board, FenOptions};
+use brainybishop::epd::Epd;
 use brainybishop::movegen::generate_moves;
+use brainybishop::perft::{perft, perft_with, PerftOptions};
 
-fn perft(board: Board, depth: u32) -> u64 {
-    if depth == 0 {
-        return 1;
-    }
+// Depths up to this many nodes run in a normal `cargo test`, the rest with
+// `cargo test --release -- --ignored`
+const SHALLOW_NODES: u64 = 2_000_000;
 
-    let moves = generate_moves(&board);
-    if depth == 1 {
-        return moves.len() as u64;
-    }
+struct PerftCase {
+    id: String,
+    board: Board,
+    // (depth, nodes), from the D1, D2, ... operations
+    counts: Vec<(u32, u64)>,
+}
 
-    let mut nodes = 0u64;
-    for mv in moves.iter() {
-        let new_board = board.make_move(*mv);
-        nodes += perft(new_board, depth - 1);
-    }
+const STANDARD: &str = include_str!("data/perft.epd");
+// Shredder-FEN castling fields, checks arbitrary king and rook files
+const CHESS960: &str = include_str!("data/perft960.epd");
+
+fn perft_suite(epd: &str) -> Vec<PerftCase> {
+    epd.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let epd = Epd::parse(line).unwrap_or_else(|e| panic!("{}: {}", line, e));
+            let counts = epd
+                .operations
+                .iter()
+                .filter_map(|(opcode, operands)| {
+                    let depth = opcode.strip_prefix('D')?.parse().ok()?;
+                    Some((depth, operands[0].parse().unwrap()))
+                })
+                .collect();
 
-    nodes
+            PerftCase {
+                id: epd.id.expect("every perft position has an id"),
+                board: epd.board,
+                counts,
+            }
+        })
+        .collect()
+}
+
+// Every position reachable within `depth` plies survives a FEN round trip
+fn check_fen_round_trip(board: Board, depth: u32) {
+    let always = FenOptions {
+        always_en_passant: true,
+        ..FenOptions::default()
+    };
+    let fen = board.to_fen_with(always);
+    assert_eq!(Board::from_fen(&fen).unwrap(), board, "{}", fen);
+
+    let canonical = board.to_fen();
+    assert_eq!(Board::from_fen(&canonical).unwrap().to_fen(), canonical);
+
+    if depth > 0 {
+        for mv in generate_moves(&board).iter() {
+            check_fen_round_trip(board.make_move(*mv), depth - 1);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -29,10 +71,10 @@ mod tests {
         let board = Board::default();
         
         // Depth 1: 20 moves
-        assert_eq!(perft(board, 1), 20);
+        assert_eq!(perft(&board, 1), 20);
         
         // Depth 2: 400 moves
-        assert_eq!(perft(board, 2), 400);
+        assert_eq!(perft(&board, 2), 400);
     }
 
     #[test]
@@ -40,7 +82,7 @@ mod tests {
         let board = Board::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 1").unwrap();
         
         // Depth 1: 31 moves (including en passant capture)
-        assert_eq!(perft(board, 1), 31);
+        assert_eq!(perft(&board, 1), 31);
     }
 
     #[test]
@@ -48,10 +90,45 @@ mod tests {
         let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
         
         // Depth 1: 48 moves
-        assert_eq!(perft(board, 1), 48);
+        assert_eq!(perft(&board, 1), 48);
         
         // Depth 2: 2039 moves
-        assert_eq!(perft(board, 2), 2039);
+        assert_eq!(perft(&board, 2), 2039);
+    }
+
+    #[test]
+    fn test_perft_suite() {
+        for case in perft_suite(STANDARD) {
+            for &(depth, nodes) in case.counts.iter().filter(|(_, n)| *n <= SHALLOW_NODES) {
+                assert_eq!(perft(&case.board, depth), nodes, "{} depth {}", case.id, depth);
+            }
+        }
+    }
+
+    #[test]
+    fn test_perft_suite_chess960() {
+        for case in perft_suite(CHESS960) {
+            for &(depth, nodes) in case.counts.iter().filter(|(_, n)| *n <= SHALLOW_NODES) {
+                assert_eq!(perft(&case.board, depth), nodes, "{} depth {}", case.id, depth);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn test_perft_suite_deep() {
+        let options = PerftOptions {
+            hash_mb: 256,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            ..PerftOptions::default()
+        };
+
+        for case in perft_suite(STANDARD).into_iter().chain(perft_suite(CHESS960)) {
+            for &(depth, nodes) in case.counts.iter().filter(|(_, n)| *n > SHALLOW_NODES) {
+                let counted = perft_with(&case.board, depth, options);
+                assert_eq!(counted, nodes, "{} depth {}", case.id, depth);
+            }
+        }
     }
 
     #[test]
@@ -68,4 +145,18 @@ mod tests {
         move_strings.dedup();
         assert_eq!(move_strings.len(), 20);
     }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 0 9",
+            "1rqbkrbn/1ppppp1p/1n6/p1N3p1/8/2P4P/PP1PPPP1/1RQBKRBN w FBfb - 0 9",
+        ];
+        for fen in fens {
+            check_fen_round_trip(Board::from_fen(fen).unwrap(), 2);
+        }
+    }
 }